@@ -0,0 +1,7 @@
+// `sfo_log_backend` is set when the logging backend is compiled in: the default `_log` feature without `nolog`.
+fn main() {
+    println!("cargo:rustc-check-cfg=cfg(sfo_log_backend)");
+    if std::env::var_os("CARGO_FEATURE__LOG").is_some() && std::env::var_os("CARGO_FEATURE_NOLOG").is_none() {
+        println!("cargo:rustc-cfg=sfo_log_backend");
+    }
+}
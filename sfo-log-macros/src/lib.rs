@@ -0,0 +1,236 @@
+use proc_macro::TokenStream;
+
+/// Runs the function inside a span named after it, with its arguments as `Debug` fields.
+///
+/// Accepts `level = "debug"`, `name = "..."`, `target = "..."`, `skip(arg, ...)`, `skip_all`,
+/// `fields(key = value, ...)`, `err`, `err(Debug)`, `ret` and `ret(Display)`.
+/// With the `nolog` feature of sfo-log the function is left unchanged.
+#[proc_macro_attribute]
+pub fn instrument(attr: TokenStream, item: TokenStream) -> TokenStream {
+    #[cfg(feature = "nolog")]
+    {
+        let _ = attr;
+        item
+    }
+    #[cfg(not(feature = "nolog"))]
+    {
+        expand::instrument(attr.into(), item.into())
+            .unwrap_or_else(|e| e.to_compile_error())
+            .into()
+    }
+}
+
+#[cfg(not(feature = "nolog"))]
+mod expand {
+    use proc_macro2::{Span, TokenStream};
+    use quote::{quote, ToTokens};
+    use syn::ext::IdentExt;
+    use syn::parse::{ParseStream, Parser};
+    use syn::punctuated::Punctuated;
+    use syn::{Error, Expr, ExprLit, FnArg, GenericArgument, Ident, ItemFn, Lit, Meta, Pat, PathArguments, ReturnType, Token, Type};
+
+    #[derive(Clone, Copy)]
+    enum Mode {
+        Debug,
+        Display,
+    }
+
+    #[derive(Default)]
+    struct Args {
+        level: Option<TokenStream>,
+        name: Option<String>,
+        target: Option<TokenStream>,
+        skips: Vec<Ident>,
+        skip_all: bool,
+        fields: Option<TokenStream>,
+        err: Option<Mode>,
+        ret: Option<Mode>,
+    }
+
+    fn parse_level(value: &Expr) -> syn::Result<TokenStream> {
+        let level = match value {
+            Expr::Lit(lit) => match &lit.lit {
+                Lit::Str(v) => v.value(),
+                Lit::Int(v) => v.base10_digits().to_string(),
+                _ => return Err(Error::new_spanned(value, "expected a level such as \"debug\"")),
+            },
+            // `Level::DEBUG` and similar paths are used as they are.
+            Expr::Path(_) => return Ok(value.to_token_stream()),
+            _ => return Err(Error::new_spanned(value, "expected a level such as \"debug\"")),
+        };
+        let level = match level.to_ascii_lowercase().as_str() {
+            "error" | "1" => quote!(ERROR),
+            "warn" | "2" => quote!(WARN),
+            "info" | "3" => quote!(INFO),
+            "debug" | "4" => quote!(DEBUG),
+            "trace" | "5" => quote!(TRACE),
+            _ => return Err(Error::new_spanned(value, "unknown level, expected error, warn, info, debug or trace")),
+        };
+        Ok(quote!(::sfo_log::Level::#level))
+    }
+
+    fn parse_mode(meta: &Meta) -> syn::Result<Mode> {
+        match meta {
+            Meta::Path(_) => Ok(Mode::Debug),
+            Meta::List(list) => {
+                let mode: Ident = list.parse_args()?;
+                match mode.to_string().as_str() {
+                    "Debug" => Ok(Mode::Debug),
+                    "Display" => Ok(Mode::Display),
+                    _ => Err(Error::new_spanned(mode, "expected Debug or Display")),
+                }
+            }
+            Meta::NameValue(_) => Err(Error::new_spanned(meta, "expected Debug or Display")),
+        }
+    }
+
+    fn parse_args(attr: TokenStream) -> syn::Result<Args> {
+        let mut args = Args::default();
+        for meta in Punctuated::<Meta, Token![,]>::parse_terminated.parse2(attr)? {
+            let key = meta.path().get_ident().map(|v| v.to_string()).unwrap_or_default();
+            match (key.as_str(), &meta) {
+                ("level", Meta::NameValue(v)) => args.level = Some(parse_level(&v.value)?),
+                ("name", Meta::NameValue(v)) => match &v.value {
+                    Expr::Lit(ExprLit { lit: Lit::Str(name), .. }) => args.name = Some(name.value()),
+                    _ => return Err(Error::new_spanned(&v.value, "expected a string")),
+                },
+                ("target", Meta::NameValue(v)) => args.target = Some(v.value.to_token_stream()),
+                // `parse_any` also accepts `self`, which a plain `Ident` rejects as a keyword.
+                ("skip", Meta::List(list)) => {
+                    args.skips.extend(list.parse_args_with(|input: ParseStream| {
+                        Punctuated::<Ident, Token![,]>::parse_terminated_with(input, Ident::parse_any)
+                    })?);
+                }
+                ("skip_all", Meta::Path(_)) => args.skip_all = true,
+                ("fields", Meta::List(list)) => args.fields = Some(list.tokens.clone()),
+                ("err", _) => args.err = Some(parse_mode(&meta)?),
+                ("ret", _) => args.ret = Some(parse_mode(&meta)?),
+                _ => return Err(Error::new_spanned(&meta, "unknown instrument argument")),
+            }
+        }
+        Ok(args)
+    }
+
+    // `impl Trait` can't be written on a binding, also when nested like in `Result<impl Display, E>`.
+    fn has_impl_trait(ty: &Type) -> bool {
+        match ty {
+            Type::ImplTrait(_) => true,
+            Type::Group(v) => has_impl_trait(&v.elem),
+            Type::Paren(v) => has_impl_trait(&v.elem),
+            Type::Reference(v) => has_impl_trait(&v.elem),
+            Type::Ptr(v) => has_impl_trait(&v.elem),
+            Type::Array(v) => has_impl_trait(&v.elem),
+            Type::Slice(v) => has_impl_trait(&v.elem),
+            Type::Tuple(v) => v.elems.iter().any(has_impl_trait),
+            Type::Path(v) => v.path.segments.iter().any(|segment| match &segment.arguments {
+                PathArguments::AngleBracketed(args) => args.args.iter().any(|arg| match arg {
+                    GenericArgument::Type(ty) => has_impl_trait(ty),
+                    _ => false,
+                }),
+                _ => false,
+            }),
+            _ => false,
+        }
+    }
+
+    fn record(mode: Mode, name: TokenStream, value: TokenStream) -> TokenStream {
+        match mode {
+            Mode::Debug => quote!(#name = ?#value),
+            Mode::Display => quote!(#name = %#value),
+        }
+    }
+
+    pub(crate) fn instrument(attr: TokenStream, item: TokenStream) -> syn::Result<TokenStream> {
+        let args = parse_args(attr)?;
+        let ItemFn { attrs, vis, sig, block } = syn::parse2::<ItemFn>(item)?;
+
+        let level = args.level.unwrap_or_else(|| quote!(::sfo_log::Level::INFO));
+        let target = args.target.unwrap_or_else(|| quote!(module_path!()));
+        let name = args.name.unwrap_or_else(|| sig.ident.to_string());
+
+        let mut fields = Vec::new();
+        if !args.skip_all {
+            for input in sig.inputs.iter() {
+                let ident = match input {
+                    FnArg::Receiver(_) => Ident::new("self", Span::call_site()),
+                    FnArg::Typed(arg) => match arg.pat.as_ref() {
+                        Pat::Ident(pat) => pat.ident.clone(),
+                        // Destructured arguments have no single name to record.
+                        _ => continue,
+                    },
+                };
+                if args.skips.contains(&ident) {
+                    continue;
+                }
+                fields.push(quote!(#ident = ?#ident));
+            }
+        }
+        if let Some(extra) = args.fields {
+            fields.push(extra);
+        }
+
+        // The type is left to inference for `impl Trait` returns.
+        let result_type = match &sig.output {
+            ReturnType::Type(_, ty) if !has_impl_trait(ty) => Some(quote!(: #ty)),
+            _ => None,
+        };
+        let mut report = Vec::new();
+        if let Some(mode) = args.err {
+            let error = record(mode, quote!(error), quote!(e));
+            let ok = match args.ret {
+                Some(mode) => {
+                    let value = record(mode, quote!(return), quote!(v));
+                    quote!(Ok(v) => ::sfo_log::event!(target: #target, #level, #value),)
+                }
+                None => quote!(Ok(_) => {}),
+            };
+            report.push(quote! {
+                match &__sfo_log_result {
+                    #ok
+                    Err(e) => ::sfo_log::event!(target: #target, ::sfo_log::Level::ERROR, #error),
+                }
+            });
+        } else if let Some(mode) = args.ret {
+            let value = record(mode, quote!(return), quote!(__sfo_log_result));
+            report.push(quote!(::sfo_log::event!(target: #target, #level, #value);));
+        }
+
+        let span = quote!(::sfo_log::span!(target: #target, #level, #name, #(#fields),*));
+        let body = if sig.asyncness.is_some() {
+            if report.is_empty() {
+                quote!(::sfo_log::Instrument::instrument(async move #block, #span).await)
+            } else {
+                quote! {
+                    ::sfo_log::Instrument::instrument(async move {
+                        let __sfo_log_result #result_type = async move #block.await;
+                        #(#report)*
+                        __sfo_log_result
+                    }, #span).await
+                }
+            }
+        } else if report.is_empty() {
+            let stmts = &block.stmts;
+            quote! {
+                let __sfo_log_span = #span;
+                let __sfo_log_guard = __sfo_log_span.enter();
+                #(#stmts)*
+            }
+        } else {
+            quote! {
+                let __sfo_log_span = #span;
+                let __sfo_log_guard = __sfo_log_span.enter();
+                #[allow(clippy::redundant_closure_call)]
+                let __sfo_log_result #result_type = (move || #block)();
+                #(#report)*
+                __sfo_log_result
+            }
+        };
+
+        Ok(quote! {
+            #(#attrs)*
+            #vis #sig {
+                #body
+            }
+        })
+    }
+}
@@ -0,0 +1,347 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, OnceLock, RwLock};
+use std::thread::{JoinHandle, ThreadId};
+use chrono::Local;
+use tracing::log::{Level, Record};
+use crate::fields::{collect_fields, Fields};
+use crate::line_format::{with_origin, RecordOrigin};
+use crate::overflow::OverflowPolicy;
+use crate::sfo_logger::{read_state, SfoLogState};
+use crate::SfoLogError;
+
+// A record copied out of the caller's frame so it can be written later on the writer thread.
+struct QueuedRecord {
+    level: Level,
+    target: String,
+    module_path: Option<String>,
+    file: Option<String>,
+    line: Option<u32>,
+    message: String,
+    fields: Fields,
+    origin: RecordOrigin,
+}
+
+impl QueuedRecord {
+    fn new(record: &Record) -> Self {
+        Self {
+            level: record.level(),
+            target: record.target().to_string(),
+            module_path: record.module_path().map(|v| v.to_string()),
+            file: record.file().map(|v| v.to_string()),
+            line: record.line(),
+            message: record.args().to_string(),
+            fields: collect_fields(record.key_values()),
+            origin: RecordOrigin::current(Local::now()),
+        }
+    }
+
+    fn write(self, state: &SfoLogState) {
+        with_origin(self.origin, || {
+            state.log(&Record::builder()
+                .args(format_args!("{}", self.message))
+                .level(self.level)
+                .target(self.target.as_str())
+                .module_path(self.module_path.as_deref())
+                .file(self.file.as_deref())
+                .line(self.line)
+                .key_values(&self.fields)
+                .build())
+        })
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    records: VecDeque<QueuedRecord>,
+    // The writer thread holds records taken from the queue but not yet written.
+    writing: bool,
+    closed: bool,
+}
+
+pub(crate) struct AsyncQueue {
+    queue: Mutex<Queue>,
+    not_empty: Condvar,
+    not_full: Condvar,
+    drained: Condvar,
+    capacity: usize,
+    overflow: OverflowPolicy,
+    dropped: AtomicU64,
+    writer_thread: OnceLock<ThreadId>,
+}
+
+impl AsyncQueue {
+    fn new(capacity: usize, overflow: OverflowPolicy) -> Self {
+        Self {
+            queue: Mutex::new(Queue::default()),
+            not_empty: Condvar::new(),
+            not_full: Condvar::new(),
+            drained: Condvar::new(),
+            capacity,
+            overflow,
+            dropped: AtomicU64::new(0),
+            writer_thread: OnceLock::new(),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Queue> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // Records logged by the writer thread itself are written directly, queueing them could deadlock.
+    fn is_writer_thread(&self) -> bool {
+        self.writer_thread.get() == Some(&std::thread::current().id())
+    }
+
+    /// Queues `record`; returns `false` if the caller has to write it itself because the writer is gone.
+    pub(crate) fn push(&self, record: &Record) -> bool {
+        if self.is_writer_thread() {
+            return false;
+        }
+        let record = QueuedRecord::new(record);
+        let mut queue = self.lock();
+        loop {
+            if queue.closed {
+                return false;
+            }
+            if queue.records.len() < self.capacity {
+                break;
+            }
+            match self.overflow {
+                OverflowPolicy::Block => {
+                    queue = self.not_full.wait(queue).unwrap_or_else(|e| e.into_inner());
+                }
+                OverflowPolicy::DropNewest => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    return true;
+                }
+                OverflowPolicy::DropOldest => {
+                    queue.records.pop_front();
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    break;
+                }
+            }
+        }
+        queue.records.push_back(record);
+        drop(queue);
+        self.not_empty.notify_one();
+        true
+    }
+
+    pub(crate) fn dropped_records(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    /// Blocks until every record queued so far has been written.
+    pub(crate) fn wait_drained(&self) {
+        if self.is_writer_thread() {
+            return;
+        }
+        let mut queue = self.lock();
+        while !queue.closed && (queue.writing || !queue.records.is_empty()) {
+            queue = self.drained.wait(queue).unwrap_or_else(|e| e.into_inner());
+        }
+    }
+
+    fn close(&self) {
+        self.lock().closed = true;
+        self.not_empty.notify_all();
+        self.not_full.notify_all();
+        self.drained.notify_all();
+    }
+
+    // Hands the queued records to `write` in batches until the queue is closed and empty.
+    fn run(&self, mut write: impl FnMut(&mut Vec<QueuedRecord>)) {
+        let mut batch = Vec::new();
+        loop {
+            {
+                let mut queue = self.lock();
+                queue.writing = false;
+                if queue.records.is_empty() {
+                    self.drained.notify_all();
+                }
+                while queue.records.is_empty() && !queue.closed {
+                    queue = self.not_empty.wait(queue).unwrap_or_else(|e| e.into_inner());
+                }
+                // Closed, the remaining records are written before the thread exits.
+                if queue.records.is_empty() {
+                    return;
+                }
+                batch.extend(queue.records.drain(..));
+                queue.writing = true;
+            }
+            self.not_full.notify_all();
+
+            write(&mut batch);
+            batch.clear();
+        }
+    }
+}
+
+/// Owns the writer thread; dropping it writes the queued records and stops the thread.
+pub(crate) struct AsyncWriter {
+    queue: Arc<AsyncQueue>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl AsyncWriter {
+    pub(crate) fn spawn(capacity: usize, overflow: OverflowPolicy, state: Arc<RwLock<SfoLogState>>) -> Result<Self, SfoLogError> {
+        Self::spawn_with(capacity, overflow, move |batch| {
+            let state = read_state(state.as_ref());
+            for record in batch.drain(..) {
+                record.write(&state);
+            }
+        })
+    }
+
+    fn spawn_with(capacity: usize, overflow: OverflowPolicy, write: impl FnMut(&mut Vec<QueuedRecord>) + Send + 'static) -> Result<Self, SfoLogError> {
+        let queue = Arc::new(AsyncQueue::new(capacity, overflow));
+        let thread_queue = queue.clone();
+        let name = "sfo-log-writer";
+        let thread = std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || thread_queue.run(write))
+            .map_err(|e| SfoLogError::Thread { name, source: e })?;
+        let _ = queue.writer_thread.set(thread.thread().id());
+        Ok(Self {
+            queue,
+            thread: Some(thread),
+        })
+    }
+
+    pub(crate) fn queue(&self) -> &Arc<AsyncQueue> {
+        &self.queue
+    }
+}
+
+impl Drop for AsyncWriter {
+    fn drop(&mut self) {
+        self.queue.close();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use super::*;
+
+    fn push(queue: &AsyncQueue, message: &str) -> bool {
+        queue.push(&Record::builder().args(format_args!("{}", message)).build())
+    }
+
+    fn queued(queue: &AsyncQueue) -> Vec<String> {
+        queue.lock().records.iter().map(|v| v.message.clone()).collect()
+    }
+
+    // A writer collecting the messages, sleeping per batch so the queue fills up.
+    fn collecting_writer(capacity: usize, overflow: OverflowPolicy) -> (AsyncWriter, Arc<Mutex<Vec<String>>>) {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink = written.clone();
+        let writer = AsyncWriter::spawn_with(capacity, overflow, move |batch| {
+            std::thread::sleep(Duration::from_millis(1));
+            sink.lock().unwrap().extend(batch.drain(..).map(|v| v.message));
+        }).unwrap();
+        (writer, written)
+    }
+
+    #[test]
+    fn start_rejects_an_empty_queue() {
+        let result = crate::Logger::new("app").set_async(0, OverflowPolicy::Block).start();
+        assert!(matches!(result, Err(SfoLogError::InvalidConfig(crate::LoggerConfigError::InvalidValue { field: "async_queue_size", .. }))));
+    }
+
+    #[test]
+    fn drop_newest_keeps_queued_records() {
+        let queue = AsyncQueue::new(2, OverflowPolicy::DropNewest);
+        for message in ["a", "b", "c", "d"] {
+            assert!(push(&queue, message));
+        }
+        assert_eq!(queued(&queue), ["a", "b"]);
+        assert_eq!(queue.dropped_records(), 2);
+    }
+
+    #[test]
+    fn drop_oldest_keeps_new_records() {
+        let queue = AsyncQueue::new(2, OverflowPolicy::DropOldest);
+        for message in ["a", "b", "c", "d"] {
+            assert!(push(&queue, message));
+        }
+        assert_eq!(queued(&queue), ["c", "d"]);
+        assert_eq!(queue.dropped_records(), 2);
+    }
+
+    #[test]
+    fn closed_queue_hands_records_back() {
+        let queue = AsyncQueue::new(2, OverflowPolicy::Block);
+        queue.close();
+        assert!(!push(&queue, "a"));
+        assert!(queued(&queue).is_empty());
+    }
+
+    #[test]
+    fn block_waits_for_room_instead_of_dropping() {
+        let (writer, written) = collecting_writer(1, OverflowPolicy::Block);
+        let expected: Vec<String> = (0..100).map(|v| v.to_string()).collect();
+        for message in expected.iter() {
+            assert!(push(writer.queue(), message));
+        }
+        writer.queue().wait_drained();
+        assert_eq!(*written.lock().unwrap(), expected);
+        assert_eq!(writer.queue().dropped_records(), 0);
+    }
+
+    #[test]
+    fn blocked_push_resumes_once_the_writer_takes_records() {
+        let (gate, gate_rx) = mpsc::channel::<()>();
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let sink = written.clone();
+        let writer = AsyncWriter::spawn_with(1, OverflowPolicy::Block, move |batch| {
+            gate_rx.recv().unwrap();
+            sink.lock().unwrap().extend(batch.drain(..).map(|v| v.message));
+        }).unwrap();
+        let queue = writer.queue().clone();
+        push(&queue, "a");
+        let pusher = std::thread::spawn(move || {
+            push(&queue, "b");
+            push(&queue, "c");
+        });
+        // Every batch holds one record, so the writer needs a go for each of them.
+        for _ in 0..3 {
+            gate.send(()).unwrap();
+        }
+        pusher.join().unwrap();
+        writer.queue().wait_drained();
+        assert_eq!(*written.lock().unwrap(), ["a", "b", "c"]);
+        assert_eq!(writer.queue().dropped_records(), 0);
+    }
+
+    #[test]
+    fn wait_drained_returns_after_every_record_is_written() {
+        let (writer, written) = collecting_writer(1024, OverflowPolicy::Block);
+        for message in 0..50 {
+            push(writer.queue(), message.to_string().as_str());
+            if message % 10 == 9 {
+                writer.queue().wait_drained();
+                assert_eq!(written.lock().unwrap().len(), message + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn drop_writes_queued_records() {
+        let (writer, written) = collecting_writer(1024, OverflowPolicy::Block);
+        for message in 0..50 {
+            push(writer.queue(), message.to_string().as_str());
+        }
+        let queue = writer.queue().clone();
+        drop(writer);
+        assert_eq!(written.lock().unwrap().len(), 50);
+        // Records after the writer stopped are written by the caller.
+        assert!(!push(&queue, "late"));
+        queue.wait_drained();
+    }
+}
@@ -0,0 +1,840 @@
+use std::fmt;
+#[cfg(feature = "config")]
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Duration;
+use serde::{Deserialize, Deserializer};
+use crate::level::parse_level;
+use crate::route::{ModuleRoute, PredicateRoute};
+use crate::{FileNaming, FilterRule, LevelDirectives, LogFormat, LogPredicate, Logger, ModuleLogOptions, OverflowPolicy, RotationPolicy, RouteMode, SfoLogError};
+
+/// Serializable form of the [`Logger`] builder.
+///
+/// Every field is optional; missing fields keep the value already set on the builder.
+/// `.toml` and `.json` files are loaded by `LoggerConfig::from_file` with the default `config` feature.
+///
+/// ```toml
+/// app_name = "my_app"
+/// log_level = "info,my_app::net=debug"
+/// log_to_file = true
+/// log_path = "/var/log/my_app"
+/// log_file_size = "10MB"
+/// log_file_count = 10
+/// rotation = "size_or_daily" # size, daily, hourly, size_or_daily or size_or_hourly
+/// file_naming = "timestamps" # numbers or timestamps
+/// compress_rotated_files = true
+/// log_max_age = "7d" # seconds or a number with unit s, m, h or d
+/// log_max_total_size = "1GB"
+/// output_console = false
+/// format = "json" # or "text", or { template = "{ts} {level:>5} {target} - {msg}" }
+/// async_queue_size = 8192 # only read at start
+/// overflow_policy = "drop_oldest" # block, drop_newest or drop_oldest
+/// # A string is `[allow:|deny:]<target>[@<levels>]`, a table can also match the message;
+/// # levels are ranges ordered from error to trace like `debug..`, `..=warn` or `info`.
+/// filters = [
+///     "allow:hyper::client",
+///     "deny:hyper@debug..",
+///     { action = "deny", target = "my_app::net", message = "^heartbeat" },
+/// ]
+///
+/// [[module_logs]]
+/// module = "my_app::net"
+/// name = "net"
+/// mode = "main_above:warn" # duplicate, exclusive or main_above:<level>
+/// # Optional, the settings above apply to the module log when missing.
+/// log_level = "trace"
+/// log_file_size = "1MB"
+/// log_file_count = 3
+/// rotation = "size"
+/// log_max_age = "1d"
+/// log_max_total_size = "100MB"
+/// format = "json"
+/// output_console = false
+/// filters = ["quinn"]
+///
+/// [[predicate_logs]]
+/// name = "error"
+/// level = "warn" # optional, records at the level or more severe
+/// target = "my_app" # optional, records of every target when missing
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggerConfig {
+    pub app_name: Option<String>,
+    pub log_level: Option<String>,
+    pub log_to_file: Option<bool>,
+    pub log_path: Option<PathBuf>,
+    #[serde(deserialize_with = "deserialize_size")]
+    pub log_file_size: Option<u64>,
+    pub log_file_count: Option<usize>,
+    pub rotation: Option<RotationPolicy>,
+    pub file_naming: Option<FileNaming>,
+    pub compress_rotated_files: Option<bool>,
+    #[serde(deserialize_with = "deserialize_duration")]
+    pub log_max_age: Option<Duration>,
+    #[serde(deserialize_with = "deserialize_size")]
+    pub log_max_total_size: Option<u64>,
+    pub instance_id: Option<String>,
+    pub output_console: Option<bool>,
+    pub format: Option<LogFormat>,
+    pub async_queue_size: Option<usize>,
+    pub overflow_policy: Option<OverflowPolicy>,
+    pub filters: Option<Vec<FilterRule>>,
+    pub module_logs: Option<Vec<ModuleLogConfig>>,
+    pub predicate_logs: Option<Vec<PredicateLogConfig>>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ModuleLogConfig {
+    pub module: String,
+    pub name: String,
+    #[serde(default)]
+    pub mode: RouteMode,
+    #[serde(default)]
+    pub log_level: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub log_file_size: Option<u64>,
+    #[serde(default)]
+    pub log_file_count: Option<usize>,
+    #[serde(default)]
+    pub rotation: Option<RotationPolicy>,
+    #[serde(default, deserialize_with = "deserialize_duration")]
+    pub log_max_age: Option<Duration>,
+    #[serde(default, deserialize_with = "deserialize_size")]
+    pub log_max_total_size: Option<u64>,
+    #[serde(default)]
+    pub format: Option<LogFormat>,
+    #[serde(default)]
+    pub output_console: Option<bool>,
+    #[serde(default)]
+    pub filters: Vec<FilterRule>,
+}
+
+impl ModuleLogConfig {
+    fn options(&self) -> ModuleLogOptions {
+        ModuleLogOptions {
+            mode: self.mode,
+            log_level: self.log_level.clone(),
+            log_file_size: self.log_file_size,
+            log_file_count: self.log_file_count,
+            rotation: self.rotation,
+            log_max_age: self.log_max_age,
+            log_max_total_size: self.log_max_total_size,
+            format: self.format.clone(),
+            output_console: self.output_console,
+            filters: self.filters.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PredicateLogConfig {
+    pub name: String,
+    #[serde(default)]
+    pub level: Option<String>,
+    #[serde(default)]
+    pub target: Option<String>,
+}
+
+impl PredicateLogConfig {
+    fn predicate(&self) -> LogPredicate {
+        let mut predicate = LogPredicate::new();
+        if let Some(level) = self.level.as_deref().and_then(|v| parse_level(v).ok().flatten()) {
+            predicate = predicate.levels(..=level);
+        }
+        if let Some(target) = self.target.as_deref() {
+            predicate = predicate.target(target);
+        }
+        predicate
+    }
+}
+
+#[derive(Debug)]
+pub enum LoggerConfigError {
+    Io { path: PathBuf, source: std::io::Error },
+    Parse { path: PathBuf, source: Box<dyn std::error::Error + Send + Sync> },
+    UnsupportedFormat(PathBuf),
+    MissingAppName,
+    InvalidLevel { level: String, source: Box<dyn std::error::Error + Send + Sync> },
+    InvalidPath { path: PathBuf, reason: &'static str },
+    InvalidValue { field: &'static str, message: String },
+}
+
+impl fmt::Display for LoggerConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoggerConfigError::Io { path, source } => write!(f, "failed to read {}: {}", path.display(), source),
+            LoggerConfigError::Parse { path, source } => write!(f, "failed to parse {}: {}", path.display(), source),
+            LoggerConfigError::UnsupportedFormat(path) => write!(f, "unsupported config format {}, expected .toml or .json", path.display()),
+            LoggerConfigError::MissingAppName => write!(f, "app_name is required"),
+            LoggerConfigError::InvalidLevel { level, source } => write!(f, "invalid log level \"{}\": {}", level, source),
+            LoggerConfigError::InvalidPath { path, reason } => write!(f, "invalid log path {}: {}", path.display(), reason),
+            LoggerConfigError::InvalidValue { field, message } => write!(f, "invalid {}: {}", field, message),
+        }
+    }
+}
+
+impl std::error::Error for LoggerConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LoggerConfigError::Io { source, .. } => Some(source),
+            LoggerConfigError::Parse { source, .. } | LoggerConfigError::InvalidLevel { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl LoggerConfig {
+    #[cfg(feature = "config")]
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, LoggerConfigError> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path).map_err(|e| LoggerConfigError::Io {
+            path: path.to_path_buf(),
+            source: e,
+        })?;
+        let config: LoggerConfig = match path.extension().and_then(|v| v.to_str()) {
+            Some("toml") => toml::from_str(content.as_str()).map_err(|e| LoggerConfigError::Parse {
+                path: path.to_path_buf(),
+                source: e.into(),
+            })?,
+            Some("json") => serde_json::from_str(content.as_str()).map_err(|e| LoggerConfigError::Parse {
+                path: path.to_path_buf(),
+                source: e.into(),
+            })?,
+            _ => return Err(LoggerConfigError::UnsupportedFormat(path.to_path_buf())),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn validate(&self) -> Result<(), LoggerConfigError> {
+        if self.app_name.as_ref().is_some_and(|v| v.is_empty()) {
+            return Err(LoggerConfigError::MissingAppName);
+        }
+        validate_level(self.log_level.as_ref())?;
+        if let Some(path) = self.log_path.as_ref() {
+            if path.as_os_str().is_empty() {
+                return Err(LoggerConfigError::InvalidPath { path: path.clone(), reason: "path is empty" });
+            }
+            if path.exists() && !path.is_dir() {
+                return Err(LoggerConfigError::InvalidPath { path: path.clone(), reason: "not a directory" });
+            }
+        }
+        validate_format(self.format.as_ref())?;
+        validate_filters(self.filters.as_deref().unwrap_or_default())?;
+        validate_limits(self.log_file_size, self.log_file_count, self.log_max_age, self.log_max_total_size)?;
+        if self.async_queue_size == Some(0) {
+            return Err(LoggerConfigError::InvalidValue {
+                field: "async_queue_size",
+                message: "must be greater than 0".to_string(),
+            });
+        }
+        if let Some(module_logs) = self.module_logs.as_ref() {
+            for module_log in module_logs.iter() {
+                if module_log.module.is_empty() || module_log.name.is_empty() {
+                    return Err(LoggerConfigError::InvalidValue {
+                        field: "module_logs",
+                        message: "module and name must not be empty".to_string(),
+                    });
+                }
+                validate_level(module_log.log_level.as_ref())?;
+                validate_format(module_log.format.as_ref())?;
+                validate_filters(module_log.filters.as_slice())?;
+                validate_limits(module_log.log_file_size, module_log.log_file_count, module_log.log_max_age, module_log.log_max_total_size)?;
+            }
+        }
+        if let Some(predicate_logs) = self.predicate_logs.as_ref() {
+            for predicate_log in predicate_logs.iter() {
+                if predicate_log.name.is_empty() {
+                    return Err(LoggerConfigError::InvalidValue {
+                        field: "predicate_logs",
+                        message: "name must not be empty".to_string(),
+                    });
+                }
+                if let Some(level) = predicate_log.level.as_ref() {
+                    match parse_level(level) {
+                        Ok(Some(_)) => {}
+                        Ok(None) => return Err(LoggerConfigError::InvalidLevel {
+                            level: level.clone(),
+                            source: "a predicate log can't be off".into(),
+                        }),
+                        Err(message) => return Err(LoggerConfigError::InvalidLevel { level: level.clone(), source: message.into() }),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn validate_level(level: Option<&String>) -> Result<(), LoggerConfigError> {
+    if let Some(level) = level {
+        level.parse::<LevelDirectives>().map_err(|message| LoggerConfigError::InvalidLevel {
+            level: level.clone(),
+            source: message.into(),
+        })?;
+    }
+    Ok(())
+}
+
+fn validate_format(format: Option<&LogFormat>) -> Result<(), LoggerConfigError> {
+    if let Some(format) = format {
+        format.validate().map_err(|e| LoggerConfigError::InvalidValue {
+            field: "format",
+            message: e.to_string(),
+        })?;
+    }
+    Ok(())
+}
+
+// Message patterns are regexes, which are only compiled with a logging backend.
+fn validate_filters(filters: &[FilterRule]) -> Result<(), LoggerConfigError> {
+    #[cfg(sfo_log_backend)]
+    crate::filter::CompiledFilter::new(filters).map_err(|e| LoggerConfigError::InvalidValue {
+        field: "filters",
+        message: e.to_string(),
+    })?;
+    #[cfg(not(sfo_log_backend))]
+    let _ = filters;
+    Ok(())
+}
+
+fn validate_limits(file_size: Option<u64>, file_count: Option<usize>, max_age: Option<Duration>, max_total_size: Option<u64>) -> Result<(), LoggerConfigError> {
+    let field = if file_size == Some(0) {
+        "log_file_size"
+    } else if file_count == Some(0) {
+        "log_file_count"
+    } else if max_age == Some(Duration::ZERO) {
+        "log_max_age"
+    } else if max_total_size == Some(0) {
+        "log_max_total_size"
+    } else {
+        return Ok(());
+    };
+    Err(LoggerConfigError::InvalidValue {
+        field,
+        message: "must be greater than 0".to_string(),
+    })
+}
+
+const ENV_LOG_LEVEL: &str = "SFO_LOG_LEVEL";
+const ENV_LOG_TO_FILE: &str = "SFO_LOG_TO_FILE";
+const ENV_LOG_PATH: &str = "SFO_LOG_PATH";
+const ENV_LOG_FILE_SIZE: &str = "SFO_LOG_FILE_SIZE";
+const ENV_LOG_FILE_COUNT: &str = "SFO_LOG_FILE_COUNT";
+const ENV_LOG_ROTATION: &str = "SFO_LOG_ROTATION";
+const ENV_LOG_FILE_NAMING: &str = "SFO_LOG_FILE_NAMING";
+const ENV_LOG_COMPRESS: &str = "SFO_LOG_COMPRESS";
+const ENV_LOG_MAX_AGE: &str = "SFO_LOG_MAX_AGE";
+const ENV_LOG_MAX_TOTAL_SIZE: &str = "SFO_LOG_MAX_TOTAL_SIZE";
+const ENV_LOG_CONSOLE: &str = "SFO_LOG_CONSOLE";
+const ENV_LOG_FORMAT: &str = "SFO_LOG_FORMAT";
+const ENV_LOG_ASYNC_QUEUE_SIZE: &str = "SFO_LOG_ASYNC_QUEUE_SIZE";
+const ENV_LOG_OVERFLOW_POLICY: &str = "SFO_LOG_OVERFLOW_POLICY";
+const ENV_LOG_FILTERS: &str = "SFO_LOG_FILTERS";
+const ENV_LOG_MODULES: &str = "SFO_LOG_MODULES";
+const ENV_LOG_INSTANCE_ID: &str = "SFO_LOG_INSTANCE_ID";
+
+impl LoggerConfig {
+    /// Reads the `SFO_LOG_*` variables, see [`Logger::with_env_overrides`].
+    pub fn from_env() -> Result<Self, LoggerConfigError> {
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    // Variables are looked up through `var`, empty ones count as unset.
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Result<Self, LoggerConfigError> {
+        let env_var = |name: &str| var(name).filter(|v| !v.trim().is_empty());
+        let config = LoggerConfig {
+            app_name: None,
+            log_level: env_var(ENV_LOG_LEVEL),
+            log_to_file: env_var(ENV_LOG_TO_FILE).map(|v| parse_bool(ENV_LOG_TO_FILE, v.as_str())).transpose()?,
+            log_path: env_var(ENV_LOG_PATH).map(PathBuf::from),
+            log_file_size: env_var(ENV_LOG_FILE_SIZE).map(|v| parse_size(v.as_str()).map_err(|message| LoggerConfigError::InvalidValue {
+                field: ENV_LOG_FILE_SIZE,
+                message,
+            })).transpose()?,
+            log_file_count: env_var(ENV_LOG_FILE_COUNT).map(|v| v.trim().parse().map_err(|_| LoggerConfigError::InvalidValue {
+                field: ENV_LOG_FILE_COUNT,
+                message: format!("invalid number \"{}\"", v),
+            })).transpose()?,
+            rotation: env_var(ENV_LOG_ROTATION).map(|v| v.parse().map_err(|message| LoggerConfigError::InvalidValue {
+                field: ENV_LOG_ROTATION,
+                message,
+            })).transpose()?,
+            file_naming: env_var(ENV_LOG_FILE_NAMING).map(|v| v.parse().map_err(|message| LoggerConfigError::InvalidValue {
+                field: ENV_LOG_FILE_NAMING,
+                message,
+            })).transpose()?,
+            compress_rotated_files: env_var(ENV_LOG_COMPRESS).map(|v| parse_bool(ENV_LOG_COMPRESS, v.as_str())).transpose()?,
+            log_max_age: env_var(ENV_LOG_MAX_AGE).map(|v| parse_duration(v.as_str()).map_err(|message| LoggerConfigError::InvalidValue {
+                field: ENV_LOG_MAX_AGE,
+                message,
+            })).transpose()?,
+            log_max_total_size: env_var(ENV_LOG_MAX_TOTAL_SIZE).map(|v| parse_size(v.as_str()).map_err(|message| LoggerConfigError::InvalidValue {
+                field: ENV_LOG_MAX_TOTAL_SIZE,
+                message,
+            })).transpose()?,
+            instance_id: env_var(ENV_LOG_INSTANCE_ID),
+            output_console: env_var(ENV_LOG_CONSOLE).map(|v| parse_bool(ENV_LOG_CONSOLE, v.as_str())).transpose()?,
+            format: env_var(ENV_LOG_FORMAT).map(|v| v.parse().map_err(|message| LoggerConfigError::InvalidValue {
+                field: ENV_LOG_FORMAT,
+                message,
+            })).transpose()?,
+            async_queue_size: env_var(ENV_LOG_ASYNC_QUEUE_SIZE).map(|v| v.trim().parse().map_err(|_| LoggerConfigError::InvalidValue {
+                field: ENV_LOG_ASYNC_QUEUE_SIZE,
+                message: format!("invalid number \"{}\"", v),
+            })).transpose()?,
+            overflow_policy: env_var(ENV_LOG_OVERFLOW_POLICY).map(|v| v.parse().map_err(|message| LoggerConfigError::InvalidValue {
+                field: ENV_LOG_OVERFLOW_POLICY,
+                message,
+            })).transpose()?,
+            filters: env_var(ENV_LOG_FILTERS).map(|v| split_list(v.as_str()).map(|rule| rule.parse().map_err(|message| LoggerConfigError::InvalidValue {
+                field: ENV_LOG_FILTERS,
+                message,
+            })).collect::<Result<Vec<_>, _>>()).transpose()?,
+            module_logs: env_var(ENV_LOG_MODULES).map(|v| {
+                split_list(v.as_str()).map(|route| {
+                    let Some((module, name)) = route.split_once('=') else {
+                        return Err(LoggerConfigError::InvalidValue {
+                            field: ENV_LOG_MODULES,
+                            message: format!("expected module=name[:mode], got \"{}\"", route),
+                        });
+                    };
+                    let (name, mode) = match name.split_once(':') {
+                        Some((name, mode)) => (name, mode.parse().map_err(|message| LoggerConfigError::InvalidValue {
+                            field: ENV_LOG_MODULES,
+                            message,
+                        })?),
+                        None => (name, RouteMode::Duplicate),
+                    };
+                    Ok(ModuleLogConfig {
+                        module: module.trim().to_string(),
+                        name: name.trim().to_string(),
+                        mode,
+                        ..ModuleLogConfig::default()
+                    })
+                }).collect::<Result<Vec<_>, _>>()
+            }).transpose()?,
+            predicate_logs: None,
+        };
+        config.validate()?;
+        Ok(config)
+    }
+}
+
+fn parse_bool(field: &'static str, value: &str) -> Result<bool, LoggerConfigError> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Ok(true),
+        "0" | "false" | "no" | "off" => Ok(false),
+        _ => Err(LoggerConfigError::InvalidValue {
+            field,
+            message: format!("invalid bool \"{}\"", value),
+        }),
+    }
+}
+
+fn split_list(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(|v| v.trim()).filter(|v| !v.is_empty())
+}
+
+impl Logger {
+    pub fn from_config(config: &LoggerConfig) -> Result<Self, SfoLogError> {
+        let app_name = config.app_name.as_ref().ok_or(LoggerConfigError::MissingAppName)?;
+        Logger::new(app_name.as_str()).apply_config(config)
+    }
+
+    #[cfg(feature = "config")]
+    pub fn from_config_file(path: impl AsRef<Path>) -> Result<Self, SfoLogError> {
+        Self::from_config(&LoggerConfig::from_file(path)?)
+    }
+
+    /// Overrides the current settings with the `SFO_LOG_*` environment variables.
+    ///
+    /// | Variable | Setting |
+    /// |---|---|
+    /// | `SFO_LOG_LEVEL` | log level spec, `RUST_LOG` still takes precedence |
+    /// | `SFO_LOG_TO_FILE` | `true`/`false` |
+    /// | `SFO_LOG_PATH` | log directory |
+    /// | `SFO_LOG_FILE_SIZE` | bytes or `"10MB"` |
+    /// | `SFO_LOG_FILE_COUNT` | number of kept files |
+    /// | `SFO_LOG_ROTATION` | `size`, `daily`, `hourly`, `size_or_daily` or `size_or_hourly` |
+    /// | `SFO_LOG_FILE_NAMING` | `numbers` or `timestamps` |
+    /// | `SFO_LOG_COMPRESS` | `true`/`false` |
+    /// | `SFO_LOG_MAX_AGE` | seconds or `"7d"`, `"12h"`, `"30m"` |
+    /// | `SFO_LOG_MAX_TOTAL_SIZE` | bytes or `"1GB"` |
+    /// | `SFO_LOG_CONSOLE` | `true`/`false` |
+    /// | `SFO_LOG_FORMAT` | `text`, `json` or a template |
+    /// | `SFO_LOG_ASYNC_QUEUE_SIZE` | queue size, enables the async writer |
+    /// | `SFO_LOG_OVERFLOW_POLICY` | `block`, `drop_newest` or `drop_oldest` |
+    /// | `SFO_LOG_FILTERS` | comma separated filter rules, e.g. `allow:hyper::client,hyper,h2@debug..` |
+    /// | `SFO_LOG_MODULES` | comma separated routes with an optional mode, e.g. `my_app::net=net:exclusive,my_app::db=db` |
+    /// | `SFO_LOG_INSTANCE_ID` | instance id |
+    ///
+    /// Call it after loading the config file to get the precedence
+    /// code defaults < config file < environment. The overrides are reapplied after every reload
+    /// of a watched config file.
+    pub fn with_env_overrides(self) -> Result<Self, SfoLogError> {
+        let env_config = LoggerConfig::from_env()?;
+        let mut logger = self.apply_config(&env_config)?;
+        logger.env_config = Some(env_config);
+        Ok(logger)
+    }
+
+    /// Overrides the current settings with every field present in `config`.
+    ///
+    /// Lists replace what was set before: `filters` replaces the rules added with [`Logger::add_filter_rule`],
+    /// `module_logs` and `predicate_logs` the logs added in code.
+    pub fn apply_config(mut self, config: &LoggerConfig) -> Result<Self, SfoLogError> {
+        config.validate()?;
+        if let Some(app_name) = config.app_name.as_ref() {
+            self.app_name = app_name.clone();
+        }
+        if let Some(log_level) = config.log_level.as_ref() {
+            self.log_level = log_level.clone();
+        }
+        if let Some(log_to_file) = config.log_to_file {
+            self.log_to_file = log_to_file;
+        }
+        if let Some(log_path) = config.log_path.as_ref() {
+            self.log_path = Some(log_path.clone());
+        }
+        if let Some(log_file_size) = config.log_file_size {
+            self.log_file_size = log_file_size;
+        }
+        if let Some(log_file_count) = config.log_file_count {
+            self.log_file_count = log_file_count;
+        }
+        if let Some(rotation) = config.rotation {
+            self.rotation = rotation;
+        }
+        if let Some(file_naming) = config.file_naming {
+            self.file_naming = file_naming;
+        }
+        if let Some(compress) = config.compress_rotated_files {
+            self.compress_rotated_files = compress;
+        }
+        if let Some(max_age) = config.log_max_age {
+            self.log_max_age = Some(max_age);
+        }
+        if let Some(max_total_size) = config.log_max_total_size {
+            self.log_max_total_size = Some(max_total_size);
+        }
+        if let Some(instance_id) = config.instance_id.as_ref() {
+            self.instance_id = instance_id.clone();
+        }
+        if let Some(output_console) = config.output_console {
+            self.output_console = output_console;
+        }
+        if let Some(format) = config.format.as_ref() {
+            self.format = format.clone();
+        }
+        if let Some(async_queue_size) = config.async_queue_size {
+            self.async_queue_size = Some(async_queue_size);
+        }
+        if let Some(overflow_policy) = config.overflow_policy {
+            self.overflow_policy = overflow_policy;
+        }
+        if let Some(filters) = config.filters.as_ref() {
+            self.filter = filters.clone();
+        }
+        if let Some(module_logs) = config.module_logs.as_ref() {
+            self.module_logs = module_logs.iter().map(|v| ModuleRoute {
+                module: v.module.clone(),
+                name: v.name.clone(),
+                options: v.options(),
+            }).collect();
+        }
+        if let Some(predicate_logs) = config.predicate_logs.as_ref() {
+            self.predicate_logs = predicate_logs.iter()
+                .map(|v| PredicateRoute::new(v.name.as_str(), v.predicate(), ModuleLogOptions::default()))
+                .collect();
+        }
+        Ok(self)
+    }
+}
+
+// Accepts either a plain number of bytes or a string such as "512KB", "10MB" or "1GB".
+fn deserialize_size<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<u64>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Size {
+        Bytes(u64),
+        Text(String),
+    }
+
+    match Option::<Size>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Size::Bytes(size)) => Ok(Some(size)),
+        Some(Size::Text(text)) => parse_size(text.as_str()).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+pub(crate) fn parse_size(text: &str) -> Result<u64, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().map_err(|_| format!("invalid size \"{}\"", text))?;
+    let multiplier = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1024,
+        "M" | "MB" => 1024 * 1024,
+        "G" | "GB" => 1024 * 1024 * 1024,
+        _ => return Err(format!("invalid size unit \"{}\"", unit)),
+    };
+    number.checked_mul(multiplier).ok_or_else(|| format!("size \"{}\" is too large", text))
+}
+
+// Accepts either a plain number of seconds or a string such as "90s", "30m", "12h" or "7d".
+fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Age {
+        Seconds(u64),
+        Text(String),
+    }
+
+    match Option::<Age>::deserialize(deserializer)? {
+        None => Ok(None),
+        Some(Age::Seconds(seconds)) => Ok(Some(Duration::from_secs(seconds))),
+        Some(Age::Text(text)) => parse_duration(text.as_str()).map(Some).map_err(serde::de::Error::custom),
+    }
+}
+
+pub(crate) fn parse_duration(text: &str) -> Result<Duration, String> {
+    let text = text.trim();
+    let split = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let number: u64 = number.parse().map_err(|_| format!("invalid duration \"{}\"", text))?;
+    let multiplier = match unit.trim().to_ascii_lowercase().as_str() {
+        "" | "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("invalid duration unit \"{}\"", unit)),
+    };
+    number.checked_mul(multiplier).map(Duration::from_secs).ok_or_else(|| format!("duration \"{}\" is too large", text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "config")]
+    fn parse(toml: &str) -> LoggerConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[cfg(feature = "config")]
+    fn invalid_field(config: &LoggerConfig) -> &'static str {
+        match config.validate() {
+            Err(LoggerConfigError::InvalidValue { field, .. }) => field,
+            other => panic!("expected an invalid value, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size(" 512 B "), Ok(512));
+        assert_eq!(parse_size("4k"), Ok(4 * 1024));
+        assert_eq!(parse_size("10MB"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("1gb"), Ok(1024 * 1024 * 1024));
+        assert_eq!(parse_size("MB"), Err("invalid size \"MB\"".to_string()));
+        assert_eq!(parse_size("1.5MB"), Err("invalid size unit \".5MB\"".to_string()));
+        assert_eq!(parse_size("10TB"), Err("invalid size unit \"TB\"".to_string()));
+        assert_eq!(parse_size("99999999999999999GB"), Err("size \"99999999999999999GB\" is too large".to_string()));
+    }
+
+    #[test]
+    fn durations() {
+        assert_eq!(parse_duration("90"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("30m"), Ok(Duration::from_secs(30 * 60)));
+        assert_eq!(parse_duration("12H"), Ok(Duration::from_secs(12 * 60 * 60)));
+        assert_eq!(parse_duration("7 d"), Ok(Duration::from_secs(7 * 24 * 60 * 60)));
+        assert_eq!(parse_duration("d"), Err("invalid duration \"d\"".to_string()));
+        assert_eq!(parse_duration("2w"), Err("invalid duration unit \"w\"".to_string()));
+        assert!(parse_duration("99999999999999999999d").is_err());
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn parses_every_field() {
+        let config = parse(r#"
+            app_name = "my_app"
+            log_level = "info,my_app::net=debug"
+            log_to_file = true
+            log_file_size = "10MB"
+            log_file_count = 10
+            rotation = "size_or_daily"
+            file_naming = "timestamps"
+            compress_rotated_files = true
+            log_max_age = "7d"
+            log_max_total_size = 1048576
+            output_console = false
+            format = { template = "{ts} {level:>5} {target} - {msg}" }
+            async_queue_size = 8192
+            overflow_policy = "drop_oldest"
+            filters = ["allow:hyper::client", "hyper", { action = "deny", message = "^heartbeat" }]
+
+            [[module_logs]]
+            module = "my_app::net"
+            name = "net"
+            mode = "main_above:warn"
+            log_file_size = "1MB"
+            log_max_age = 3600
+
+            [[predicate_logs]]
+            name = "error"
+            level = "warn"
+        "#);
+        config.validate().unwrap();
+        assert_eq!(config.log_file_size, Some(10 * 1024 * 1024));
+        assert_eq!(config.log_max_age, Some(Duration::from_secs(7 * 24 * 60 * 60)));
+        assert_eq!(config.log_max_total_size, Some(1024 * 1024));
+        assert_eq!(config.rotation, Some(RotationPolicy::SizeOrDaily));
+        assert_eq!(config.overflow_policy, Some(OverflowPolicy::DropOldest));
+        assert_eq!(config.filters.as_ref().map(|v| v.len()), Some(3));
+        let module_log = &config.module_logs.as_ref().unwrap()[0];
+        assert_eq!(module_log.mode, RouteMode::MainAbove(crate::Level::WARN));
+        assert_eq!(module_log.log_file_size, Some(1024 * 1024));
+        assert_eq!(module_log.log_max_age, Some(Duration::from_secs(3600)));
+        assert_eq!(config.predicate_logs.as_ref().unwrap()[0].level.as_deref(), Some("warn"));
+
+        let json: LoggerConfig = serde_json::from_str(r#"{"app_name": "my_app", "log_file_size": "1KB", "module_logs": [{"module": "a", "name": "b"}]}"#).unwrap();
+        json.validate().unwrap();
+        assert_eq!(json.log_file_size, Some(1024));
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn rejects_unknown_fields_and_bad_values() {
+        assert!(toml::from_str::<LoggerConfig>("log_levle = \"info\"").is_err());
+        assert!(toml::from_str::<LoggerConfig>("[[module_logs]]\nmodule = \"a\"\nname = \"b\"\nlevel = \"info\"").is_err());
+        assert!(toml::from_str::<LoggerConfig>("log_file_size = \"10XB\"").is_err());
+        assert!(toml::from_str::<LoggerConfig>("rotation = \"weekly\"").is_err());
+        assert!(toml::from_str::<LoggerConfig>("[[module_logs]]\nmodule = \"a\"\nname = \"b\"\nmode = \"sometimes\"").is_err());
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn validation_errors() {
+        assert!(matches!(parse("app_name = \"\"").validate(), Err(LoggerConfigError::MissingAppName)));
+        assert!(matches!(parse("log_level = \"info,my_app=loud\"").validate(),
+            Err(LoggerConfigError::InvalidLevel { level, .. }) if level == "info,my_app=loud"));
+        assert!(matches!(parse("log_path = \"\"").validate(), Err(LoggerConfigError::InvalidPath { reason: "path is empty", .. })));
+        let file = std::env::current_exe().unwrap();
+        let config = LoggerConfig { log_path: Some(file), ..LoggerConfig::default() };
+        assert!(matches!(config.validate(), Err(LoggerConfigError::InvalidPath { reason: "not a directory", .. })));
+
+        assert_eq!(invalid_field(&parse("format = { template = \"{lvl}\" }")), "format");
+        assert_eq!(invalid_field(&parse("log_file_size = 0")), "log_file_size");
+        assert_eq!(invalid_field(&parse("log_file_count = 0")), "log_file_count");
+        assert_eq!(invalid_field(&parse("log_max_age = \"0d\"")), "log_max_age");
+        assert_eq!(invalid_field(&parse("log_max_total_size = \"0MB\"")), "log_max_total_size");
+        assert_eq!(invalid_field(&parse("async_queue_size = 0")), "async_queue_size");
+        assert_eq!(invalid_field(&parse("[[module_logs]]\nmodule = \"\"\nname = \"net\"")), "module_logs");
+        assert_eq!(invalid_field(&parse("[[module_logs]]\nmodule = \"a\"\nname = \"b\"\nlog_file_count = 0")), "log_file_count");
+        assert_eq!(invalid_field(&parse("[[predicate_logs]]\nname = \"\"")), "predicate_logs");
+        assert!(matches!(parse("[[predicate_logs]]\nname = \"error\"\nlevel = \"off\"").validate(), Err(LoggerConfigError::InvalidLevel { .. })));
+        let error = parse("log_level = \"my_app=loud\"").validate().unwrap_err();
+        assert!(std::error::Error::source(&error).is_some_and(|v| v.to_string().contains("loud")));
+        #[cfg(sfo_log_backend)]
+        assert_eq!(invalid_field(&parse("filters = [{ action = \"deny\", message = \"(\" }]")), "filters");
+    }
+
+    #[cfg(feature = "config")]
+    #[test]
+    fn from_file_errors() {
+        let dir = std::env::temp_dir().join(format!("sfo-log-config-test-{}", std::process::id()));
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        let missing = dir.join("missing.toml");
+        assert!(matches!(LoggerConfig::from_file(missing.as_path()), Err(LoggerConfigError::Io { .. })));
+        let yaml = dir.join("log.yaml");
+        std::fs::write(yaml.as_path(), "app_name: my_app").unwrap();
+        assert!(matches!(LoggerConfig::from_file(yaml.as_path()), Err(LoggerConfigError::UnsupportedFormat(_))));
+        let broken = dir.join("log.json");
+        std::fs::write(broken.as_path(), "{\"app_name\": ").unwrap();
+        assert!(matches!(LoggerConfig::from_file(broken.as_path()), Err(LoggerConfigError::Parse { .. })));
+        // The parser error stays reachable through the chain.
+        let error = crate::SfoLogError::from(LoggerConfig::from_file(broken.as_path()).unwrap_err());
+        let source = std::error::Error::source(&error).and_then(std::error::Error::source);
+        assert!(source.is_some_and(|v| v.is::<serde_json::Error>()));
+        let broken = dir.join("broken.toml");
+        std::fs::write(broken.as_path(), "app_name = ").unwrap();
+        let error = LoggerConfig::from_file(broken.as_path()).unwrap_err();
+        assert!(std::error::Error::source(&error).is_some_and(|v| v.is::<toml::de::Error>()));
+        let invalid = dir.join("log.toml");
+        std::fs::write(invalid.as_path(), "log_file_count = 0").unwrap();
+        assert!(matches!(LoggerConfig::from_file(invalid.as_path()), Err(LoggerConfigError::InvalidValue { field: "log_file_count", .. })));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    fn from_vars(vars: &[(&str, &str)]) -> Result<LoggerConfig, LoggerConfigError> {
+        LoggerConfig::from_vars(|name| vars.iter().find(|(v, _)| *v == name).map(|(_, value)| value.to_string()))
+    }
+
+    fn env_error_field(vars: &[(&str, &str)]) -> &'static str {
+        match from_vars(vars) {
+            Err(LoggerConfigError::InvalidValue { field, .. }) => field,
+            other => panic!("expected an invalid value, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn env_vars() {
+        let config = from_vars(&[
+            ("SFO_LOG_LEVEL", "debug"),
+            ("SFO_LOG_TO_FILE", "Yes"),
+            ("SFO_LOG_FILE_SIZE", "2MB"),
+            ("SFO_LOG_FILE_COUNT", " 4 "),
+            ("SFO_LOG_MAX_AGE", "1h"),
+            ("SFO_LOG_CONSOLE", "off"),
+            ("SFO_LOG_FORMAT", "json"),
+            ("SFO_LOG_FILTERS", "allow:hyper::client, hyper@debug.."),
+            ("SFO_LOG_INSTANCE_ID", ""),
+        ]).unwrap();
+        assert_eq!(config.log_level.as_deref(), Some("debug"));
+        assert_eq!(config.log_to_file, Some(true));
+        assert_eq!(config.log_file_size, Some(2 * 1024 * 1024));
+        assert_eq!(config.log_file_count, Some(4));
+        assert_eq!(config.log_max_age, Some(Duration::from_secs(3600)));
+        assert_eq!(config.output_console, Some(false));
+        assert_eq!(config.format, Some(LogFormat::Json));
+        assert_eq!(config.filters.as_ref().map(|v| v.len()), Some(2));
+        // Empty variables count as unset.
+        assert_eq!(config.instance_id, None);
+        assert_eq!(config.log_path, None);
+    }
+
+    #[test]
+    fn env_module_routes() {
+        let config = from_vars(&[("SFO_LOG_MODULES", "my_app::net=net:exclusive, my_app::db=db,my_app::rpc = rpc : main_above:warn")]).unwrap();
+        let routes: Vec<(&str, &str, RouteMode)> = config.module_logs.as_ref().unwrap().iter()
+            .map(|v| (v.module.as_str(), v.name.as_str(), v.mode))
+            .collect();
+        assert_eq!(routes, [
+            ("my_app::net", "net", RouteMode::Exclusive),
+            ("my_app::db", "db", RouteMode::Duplicate),
+            ("my_app::rpc", "rpc", RouteMode::MainAbove(crate::Level::WARN)),
+        ]);
+        assert_eq!(env_error_field(&[("SFO_LOG_MODULES", "my_app::net")]), "SFO_LOG_MODULES");
+        assert_eq!(env_error_field(&[("SFO_LOG_MODULES", "my_app::net=net:sometimes")]), "SFO_LOG_MODULES");
+        assert_eq!(env_error_field(&[("SFO_LOG_MODULES", "=net")]), "module_logs");
+    }
+
+    #[test]
+    fn env_var_errors() {
+        assert_eq!(env_error_field(&[("SFO_LOG_TO_FILE", "maybe")]), "SFO_LOG_TO_FILE");
+        assert_eq!(env_error_field(&[("SFO_LOG_FILE_SIZE", "big")]), "SFO_LOG_FILE_SIZE");
+        assert_eq!(env_error_field(&[("SFO_LOG_FILE_COUNT", "-1")]), "SFO_LOG_FILE_COUNT");
+        assert_eq!(env_error_field(&[("SFO_LOG_ROTATION", "weekly")]), "SFO_LOG_ROTATION");
+        assert_eq!(env_error_field(&[("SFO_LOG_MAX_AGE", "1y")]), "SFO_LOG_MAX_AGE");
+        assert_eq!(env_error_field(&[("SFO_LOG_OVERFLOW_POLICY", "spill")]), "SFO_LOG_OVERFLOW_POLICY");
+        assert_eq!(env_error_field(&[("SFO_LOG_FILTERS", "hyper@loud")]), "SFO_LOG_FILTERS");
+        assert_eq!(env_error_field(&[("SFO_LOG_ASYNC_QUEUE_SIZE", "0")]), "async_queue_size");
+        assert!(matches!(from_vars(&[("SFO_LOG_LEVEL", "my_app=loud")]), Err(LoggerConfigError::InvalidLevel { .. })));
+    }
+}
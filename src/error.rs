@@ -0,0 +1,61 @@
+use std::fmt;
+use std::path::PathBuf;
+use crate::{LoggerConfigError, TemplateError};
+
+/// Errors of [`crate::Logger`] and [`crate::SfoLogHandle`].
+#[derive(Debug)]
+pub enum SfoLogError {
+    /// A level spec such as `"info,my_crate::net=debug"` was rejected.
+    InvalidLevel { spec: String, source: Box<dyn std::error::Error + Send + Sync> },
+    /// The files of a log couldn't be opened; `log` is the module log name, empty for the main log.
+    Io { log: String, path: PathBuf, source: std::io::Error },
+    /// A background thread of the logger couldn't be started.
+    Thread { name: &'static str, source: std::io::Error },
+    /// Another `log` logger was installed before [`crate::Logger::start`].
+    LoggerAlreadySet,
+    InvalidFormat(TemplateError),
+    /// The message pattern of a [`crate::FilterRule`] isn't a valid regex.
+    InvalidFilter { pattern: String, source: Box<dyn std::error::Error + Send + Sync> },
+    InvalidConfig(LoggerConfigError),
+}
+
+impl fmt::Display for SfoLogError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SfoLogError::InvalidLevel { spec, source } => write!(f, "invalid log level \"{}\": {}", spec, source),
+            SfoLogError::Io { log, path, source } if log.is_empty() => write!(f, "failed to open main log in {}: {}", path.display(), source),
+            SfoLogError::Io { log, path, source } => write!(f, "failed to open log {} in {}: {}", log, path.display(), source),
+            SfoLogError::Thread { name, source } => write!(f, "failed to start thread {}: {}", name, source),
+            SfoLogError::LoggerAlreadySet => write!(f, "a logger is already set"),
+            SfoLogError::InvalidFormat(e) => write!(f, "invalid log format: {}", e),
+            SfoLogError::InvalidFilter { pattern, source } => write!(f, "invalid filter pattern \"{}\": {}", pattern, source),
+            SfoLogError::InvalidConfig(e) => write!(f, "invalid log config: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SfoLogError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SfoLogError::InvalidLevel { source, .. } => Some(source.as_ref()),
+            SfoLogError::Io { source, .. } => Some(source),
+            SfoLogError::Thread { source, .. } => Some(source),
+            SfoLogError::LoggerAlreadySet => None,
+            SfoLogError::InvalidFormat(e) => Some(e),
+            SfoLogError::InvalidFilter { source, .. } => Some(source.as_ref()),
+            SfoLogError::InvalidConfig(e) => Some(e),
+        }
+    }
+}
+
+impl From<LoggerConfigError> for SfoLogError {
+    fn from(e: LoggerConfigError) -> Self {
+        SfoLogError::InvalidConfig(e)
+    }
+}
+
+impl From<TemplateError> for SfoLogError {
+    fn from(e: TemplateError) -> Self {
+        SfoLogError::InvalidFormat(e)
+    }
+}
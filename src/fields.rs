@@ -0,0 +1,108 @@
+use std::fmt;
+use serde::Serialize;
+use tracing::log::kv::{self, Key, Source, ToValue, Value, VisitSource, VisitValue};
+
+/// A structured field value that outlives the record it was logged with.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub(crate) enum FieldValue {
+    Null,
+    Bool(bool),
+    I64(i64),
+    U64(u64),
+    F64(f64),
+    Str(String),
+}
+
+pub(crate) type Fields = Vec<(String, FieldValue)>;
+
+impl FieldValue {
+    fn from_value(value: &Value) -> Self {
+        struct Capture(FieldValue);
+
+        impl<'v> VisitValue<'v> for Capture {
+            fn visit_any(&mut self, value: Value) -> Result<(), kv::Error> {
+                self.0 = FieldValue::Str(value.to_string());
+                Ok(())
+            }
+
+            fn visit_null(&mut self) -> Result<(), kv::Error> {
+                self.0 = FieldValue::Null;
+                Ok(())
+            }
+
+            fn visit_u64(&mut self, value: u64) -> Result<(), kv::Error> {
+                self.0 = FieldValue::U64(value);
+                Ok(())
+            }
+
+            fn visit_i64(&mut self, value: i64) -> Result<(), kv::Error> {
+                self.0 = FieldValue::I64(value);
+                Ok(())
+            }
+
+            fn visit_f64(&mut self, value: f64) -> Result<(), kv::Error> {
+                self.0 = FieldValue::F64(value);
+                Ok(())
+            }
+
+            fn visit_bool(&mut self, value: bool) -> Result<(), kv::Error> {
+                self.0 = FieldValue::Bool(value);
+                Ok(())
+            }
+
+            fn visit_str(&mut self, value: &str) -> Result<(), kv::Error> {
+                self.0 = FieldValue::Str(value.to_string());
+                Ok(())
+            }
+        }
+
+        let mut capture = Capture(FieldValue::Null);
+        if value.visit(&mut capture).is_err() {
+            capture.0 = FieldValue::Str(value.to_string());
+        }
+        capture.0
+    }
+}
+
+impl fmt::Display for FieldValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FieldValue::Null => write!(f, "null"),
+            FieldValue::Bool(v) => write!(f, "{}", v),
+            FieldValue::I64(v) => write!(f, "{}", v),
+            FieldValue::U64(v) => write!(f, "{}", v),
+            FieldValue::F64(v) => write!(f, "{}", v),
+            FieldValue::Str(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+impl ToValue for FieldValue {
+    fn to_value(&self) -> Value<'_> {
+        match self {
+            FieldValue::Null => Value::null(),
+            FieldValue::Bool(v) => v.to_value(),
+            FieldValue::I64(v) => v.to_value(),
+            FieldValue::U64(v) => v.to_value(),
+            FieldValue::F64(v) => v.to_value(),
+            FieldValue::Str(v) => v.as_str().to_value(),
+        }
+    }
+}
+
+/// Copies the key/value pairs of a record, in the order they were logged.
+pub(crate) fn collect_fields(source: &dyn Source) -> Fields {
+    struct Collect(Fields);
+
+    impl<'kvs> VisitSource<'kvs> for Collect {
+        fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+            self.0.push((key.to_string(), FieldValue::from_value(&value)));
+            Ok(())
+        }
+    }
+
+    let mut collect = Collect(Vec::with_capacity(source.count()));
+    let _ = source.visit(&mut collect);
+    collect.0
+}
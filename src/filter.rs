@@ -0,0 +1,325 @@
+use std::ops::{Bound, RangeBounds};
+use serde::{Deserialize, Deserializer};
+#[cfg(sfo_log_backend)]
+use regex::Regex;
+#[cfg(sfo_log_backend)]
+use tracing::log;
+#[cfg(sfo_log_backend)]
+use crate::route::CompiledPredicate;
+use crate::level::parse_level;
+use crate::Level;
+#[cfg(sfo_log_backend)]
+use crate::SfoLogError;
+
+/// What happens to a record matched by a [`FilterRule`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FilterAction {
+    Allow,
+    Deny,
+}
+
+/// One rule of the ordered filter list of a log; the first rule matching a record decides,
+/// records matching no rule are written.
+///
+/// A rule matches records meeting all of its conditions:
+///
+/// ```no_run
+/// use sfo_log::{FilterRule, Level, Logger};
+///
+/// let _handle = Logger::new("my_app")
+///     .add_filter_rule(FilterRule::allow().target("hyper::client"))
+///     .add_filter_rule(FilterRule::deny().target("hyper").levels(Level::DEBUG..))
+///     .add_filter_rule(FilterRule::deny().message("^heartbeat"))
+///     .start()
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone)]
+#[cfg_attr(not(sfo_log_backend), allow(dead_code))]
+pub struct FilterRule {
+    pub(crate) action: FilterAction,
+    pub(crate) target: Option<String>,
+    pub(crate) levels: (Bound<Level>, Bound<Level>),
+    pub(crate) message: Option<String>,
+}
+
+impl FilterRule {
+    pub fn new(action: FilterAction) -> Self {
+        Self {
+            action,
+            target: None,
+            levels: (Bound::Unbounded, Bound::Unbounded),
+            message: None,
+        }
+    }
+
+    pub fn allow() -> Self {
+        Self::new(FilterAction::Allow)
+    }
+
+    pub fn deny() -> Self {
+        Self::new(FilterAction::Deny)
+    }
+
+    /// Matches targets starting with the `::` segments of `target`, where a `*` segment matches any one segment.
+    pub fn target(mut self, target: &str) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    /// Matches records with a level in `levels`, which are ordered from `ERROR` to `TRACE`,
+    /// e.g. `Level::DEBUG..` for debug and trace records.
+    pub fn levels(mut self, levels: impl RangeBounds<Level>) -> Self {
+        self.levels = (levels.start_bound().cloned(), levels.end_bound().cloned());
+        self
+    }
+
+    /// Matches records whose message matches the regex `pattern`; it is compiled by [`crate::Logger::start`].
+    pub fn message(mut self, pattern: &str) -> Self {
+        self.message = Some(pattern.to_string());
+        self
+    }
+}
+
+impl std::str::FromStr for FilterRule {
+    type Err = String;
+
+    // `[allow:|deny:]<target>[@<levels>]`, e.g. `hyper`, `allow:hyper::client` or `deny:hyper@debug..`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (rule, rest) = if let Some(rest) = s.strip_prefix("allow:") {
+            (FilterRule::allow(), rest)
+        } else if let Some(rest) = s.strip_prefix("deny:") {
+            (FilterRule::deny(), rest)
+        } else {
+            (FilterRule::deny(), s)
+        };
+        let (target, levels) = match rest.split_once('@') {
+            Some((target, levels)) => (target.trim(), Some(parse_levels(levels)?)),
+            None => (rest.trim(), None),
+        };
+        if target.is_empty() && levels.is_none() {
+            return Err(format!("filter rule \"{}\" matches every record, give a target or levels", s));
+        }
+        let mut rule = if target.is_empty() { rule } else { rule.target(target) };
+        if let Some(levels) = levels {
+            rule.levels = levels;
+        }
+        Ok(rule)
+    }
+}
+
+// `debug..`, `..=warn`, `warn..debug`, `info..=debug`, or a single level like `info`.
+fn parse_levels(levels: &str) -> Result<(Bound<Level>, Bound<Level>), String> {
+    if levels.trim().is_empty() {
+        return Err("missing levels after \"@\"".to_string());
+    }
+    let bound = |level: &str, bound: fn(Level) -> Bound<Level>| -> Result<Bound<Level>, String> {
+        if level.trim().is_empty() {
+            return Ok(Bound::Unbounded);
+        }
+        match parse_level(level)? {
+            Some(level) => Ok(bound(level)),
+            None => Err(format!("\"off\" can't be part of the level range \"{}\"", levels.trim())),
+        }
+    };
+    match levels.split_once("..") {
+        Some((start, end)) => match end.strip_prefix('=') {
+            Some(end) => Ok((bound(start, Bound::Included)?, bound(end, Bound::Included)?)),
+            None => Ok((bound(start, Bound::Included)?, bound(end, Bound::Excluded)?)),
+        },
+        None => {
+            let level = bound(levels, Bound::Included)?;
+            Ok((level, level))
+        }
+    }
+}
+
+// A bare string uses the `FromStr` form, a table names the fields:
+// `{ action = "allow", target = "hyper", levels = "debug..", message = "^heartbeat" }`.
+impl<'de> Deserialize<'de> for FilterRule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(deny_unknown_fields)]
+        struct Table {
+            action: FilterAction,
+            #[serde(default)]
+            target: Option<String>,
+            #[serde(default)]
+            levels: Option<String>,
+            #[serde(default)]
+            message: Option<String>,
+        }
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Text(String),
+            Table(Table),
+        }
+
+        match Repr::deserialize(deserializer)? {
+            Repr::Text(text) => text.parse().map_err(serde::de::Error::custom),
+            Repr::Table(table) => {
+                let mut rule = FilterRule::new(table.action);
+                rule.target = table.target;
+                if let Some(levels) = table.levels.as_deref() {
+                    rule.levels = parse_levels(levels).map_err(serde::de::Error::custom)?;
+                }
+                rule.message = table.message;
+                Ok(rule)
+            }
+        }
+    }
+}
+
+#[cfg(sfo_log_backend)]
+struct CompiledRule {
+    action: FilterAction,
+    predicate: CompiledPredicate,
+    message: Option<Regex>,
+}
+
+#[cfg(sfo_log_backend)]
+impl CompiledRule {
+    fn matches(&self, record: &log::Record) -> bool {
+        if !self.predicate.matches(record.metadata()) {
+            return false;
+        }
+        match self.message.as_ref() {
+            Some(message) => match record.args().as_str() {
+                Some(text) => message.is_match(text),
+                None => message.is_match(record.args().to_string().as_str()),
+            },
+            None => true,
+        }
+    }
+}
+
+/// The filter rules of one log, with targets and message patterns compiled.
+#[cfg(sfo_log_backend)]
+pub(crate) struct CompiledFilter {
+    rules: Vec<CompiledRule>,
+}
+
+#[cfg(sfo_log_backend)]
+impl CompiledFilter {
+    pub(crate) fn new(rules: &[FilterRule]) -> Result<Self, SfoLogError> {
+        let rules = rules.iter().map(|rule| {
+            Ok(CompiledRule {
+                action: rule.action,
+                predicate: CompiledPredicate::new(rule.target.as_deref(), &rule.levels),
+                message: rule.message.as_deref().map(Regex::new).transpose().map_err(|e| SfoLogError::InvalidFilter {
+                    pattern: rule.message.clone().unwrap_or_default(),
+                    source: Box::new(e),
+                })?,
+            })
+        }).collect::<Result<Vec<_>, SfoLogError>>()?;
+        Ok(Self { rules })
+    }
+
+    pub(crate) fn allows(&self, record: &log::Record) -> bool {
+        self.rules.iter()
+            .find(|rule| rule.matches(record))
+            .is_none_or(|rule| rule.action == FilterAction::Allow)
+    }
+}
+
+#[cfg(all(test, sfo_log_backend))]
+mod tests {
+    use super::*;
+
+    fn allows(filter: &CompiledFilter, level: log::Level, target: &str, message: &str) -> bool {
+        filter.allows(&log::Record::builder()
+            .level(level)
+            .target(target)
+            .args(format_args!("{}", message))
+            .build())
+    }
+
+    #[test]
+    fn no_rules_allow_everything() {
+        let filter = CompiledFilter::new(&[]).unwrap();
+        assert!(allows(&filter, log::Level::Trace, "hyper", "ping"));
+    }
+
+    #[test]
+    fn first_matching_rule_decides() {
+        let filter = CompiledFilter::new(&[
+            FilterRule::allow().target("hyper::client"),
+            FilterRule::deny().target("hyper"),
+        ]).unwrap();
+        assert!(allows(&filter, log::Level::Info, "hyper::client::pool", "ping"));
+        assert!(!allows(&filter, log::Level::Info, "hyper::proto", "ping"));
+        assert!(allows(&filter, log::Level::Info, "hyperx", "ping"));
+
+        let reversed = CompiledFilter::new(&[
+            FilterRule::deny().target("hyper"),
+            FilterRule::allow().target("hyper::client"),
+        ]).unwrap();
+        assert!(!allows(&reversed, log::Level::Info, "hyper::client::pool", "ping"));
+    }
+
+    #[test]
+    fn level_ranges() {
+        let filter = CompiledFilter::new(&[FilterRule::deny().target("hyper").levels(Level::DEBUG..)]).unwrap();
+        assert!(!allows(&filter, log::Level::Debug, "hyper", "ping"));
+        assert!(!allows(&filter, log::Level::Trace, "hyper", "ping"));
+        assert!(allows(&filter, log::Level::Info, "hyper", "ping"));
+
+        let filter = CompiledFilter::new(&[FilterRule::deny().levels(Level::WARN..Level::DEBUG)]).unwrap();
+        assert!(allows(&filter, log::Level::Error, "my_app", "ping"));
+        assert!(!allows(&filter, log::Level::Warn, "my_app", "ping"));
+        assert!(!allows(&filter, log::Level::Info, "my_app", "ping"));
+        assert!(allows(&filter, log::Level::Debug, "my_app", "ping"));
+    }
+
+    #[test]
+    fn message_patterns() {
+        let filter = CompiledFilter::new(&[
+            FilterRule::allow().message("heartbeat failed"),
+            FilterRule::deny().message("^heartbeat"),
+        ]).unwrap();
+        assert!(!allows(&filter, log::Level::Info, "my_app", "heartbeat ok"));
+        assert!(allows(&filter, log::Level::Info, "my_app", "heartbeat failed"));
+        assert!(allows(&filter, log::Level::Info, "my_app", "got heartbeat"));
+    }
+
+    #[test]
+    fn parses_rules() {
+        let rule: FilterRule = "hyper".parse().unwrap();
+        assert_eq!((rule.action, rule.target.as_deref()), (FilterAction::Deny, Some("hyper")));
+        let rule: FilterRule = "allow:hyper::client@..=warn".parse().unwrap();
+        assert_eq!((rule.action, rule.target.as_deref()), (FilterAction::Allow, Some("hyper::client")));
+        assert_eq!(rule.levels, (Bound::Unbounded, Bound::Included(Level::WARN)));
+        let rule: FilterRule = "deny:@info".parse().unwrap();
+        assert_eq!((rule.target, rule.levels), (None, (Bound::Included(Level::INFO), Bound::Included(Level::INFO))));
+        let rule: FilterRule = "h2@warn..debug".parse().unwrap();
+        assert_eq!(rule.levels, (Bound::Included(Level::WARN), Bound::Excluded(Level::DEBUG)));
+        for invalid in ["deny:", "hyper@loud", "hyper@off..", "@"] {
+            assert!(invalid.parse::<FilterRule>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn deserializes_rules() {
+        let rules: Vec<FilterRule> = serde_json::from_str(r#"[
+            "allow:hyper::client",
+            {"action": "deny", "target": "hyper", "levels": "debug.."},
+            {"action": "deny", "message": "^heartbeat"}
+        ]"#).unwrap();
+        let filter = CompiledFilter::new(&rules).unwrap();
+        assert!(allows(&filter, log::Level::Debug, "hyper::client", "ping"));
+        assert!(!allows(&filter, log::Level::Debug, "hyper::proto", "ping"));
+        assert!(allows(&filter, log::Level::Info, "hyper::proto", "ping"));
+        assert!(!allows(&filter, log::Level::Info, "my_app", "heartbeat ok"));
+        assert!(serde_json::from_str::<FilterRule>(r#"{"action": "drop"}"#).is_err());
+        assert!(serde_json::from_str::<FilterRule>(r#"{"action": "deny", "levels": "loud.."}"#).is_err());
+    }
+
+    #[test]
+    fn invalid_message_pattern() {
+        let result = CompiledFilter::new(&[FilterRule::deny().message("(")]);
+        assert!(matches!(result, Err(SfoLogError::InvalidFilter { pattern, .. }) if pattern == "("));
+    }
+}
@@ -0,0 +1,299 @@
+use std::fmt;
+#[cfg(feature = "_log")]
+use chrono::format::{Item, StrftimeItems};
+use serde::Deserialize;
+
+#[cfg(sfo_log_backend)]
+pub(crate) const TEXT_TEMPLATE: &str = "{ts} [{level}] [{module}:{file}:{line}] [{thread}] - {msg}";
+const DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+/// Layout of the lines written to the log files and the console.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// `{ts} [{level}] [{module}:{file}:{line}] [{thread}] - {msg}`
+    #[default]
+    Text,
+    /// One JSON object per line with the keys `timestamp`, `level`, `target`, `module`, `file`,
+    /// `line`, `thread`, `app_name`, `instance_id` and `message`, plus `fields` holding the
+    /// key/value pairs of the record with their types, e.g. `"fields":{"peer":"10.0.0.1","bytes":512}`.
+    ///
+    /// Fields of `tracing` macros such as `info!(bytes = n, "sent")` are passed as key/value
+    /// pairs when [`crate::SfoLogLayer`] is installed, otherwise they are part of the message.
+    Json,
+    /// A user defined layout such as `"{ts:%H:%M:%S%.3f} {level:>5} {target} {thread} {msg}"`.
+    ///
+    /// Placeholders:
+    ///
+    /// | Placeholder | Value |
+    /// |---|---|
+    /// | `{ts}`, `{ts:<strftime>}` | local time, `%Y-%m-%d %H:%M:%S` by default |
+    /// | `{level}` | `ERROR` ... `TRACE` |
+    /// | `{target}` | full record target |
+    /// | `{module}` | first segment of the target |
+    /// | `{file}`, `{path}` | source file name, full source path |
+    /// | `{line}` | source line |
+    /// | `{thread}`, `{thread_id}` | thread name (id if unnamed), thread id |
+    /// | `{pid}` | process id |
+    /// | `{app_name}`, `{instance_id}` | logger settings |
+    /// | `{msg}` | the message followed by the record's key/value pairs as `key=value` |
+    ///
+    /// Every placeholder except `{ts}` accepts a padding spec like `{level:>5}`, `{target:<20}`
+    /// or `{thread:^10}`. `{{` and `}}` produce literal braces.
+    Template(String),
+}
+
+impl std::str::FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ if s.contains('{') => Ok(LogFormat::Template(s.to_string())),
+            _ => Err(format!("unknown log format \"{}\", expected text, json or a template", s)),
+        }
+    }
+}
+
+impl LogFormat {
+    pub(crate) fn validate(&self) -> Result<(), TemplateError> {
+        if let LogFormat::Template(template) = self {
+            CompiledTemplate::compile(template.as_str())?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    template: String,
+    message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid log template \"{}\": {}", self.template, self.message)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Align {
+    Left,
+    Right,
+    Center,
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(not(sfo_log_backend), allow(dead_code))]
+pub(crate) struct Padding {
+    pub(crate) align: Align,
+    pub(crate) width: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum Field {
+    Level,
+    Target,
+    Module,
+    File,
+    Path,
+    Line,
+    Thread,
+    ThreadId,
+    Pid,
+    AppName,
+    InstanceId,
+    Message,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(not(sfo_log_backend), allow(dead_code))]
+pub(crate) enum Segment {
+    Literal(String),
+    Timestamp(String),
+    Field(Field, Option<Padding>),
+}
+
+/// A template parsed into segments, so records don't reparse it.
+#[derive(Debug, Clone)]
+// Without a logging backend templates are only compiled to validate them.
+#[cfg_attr(not(sfo_log_backend), allow(dead_code))]
+pub(crate) struct CompiledTemplate {
+    pub(crate) segments: Vec<Segment>,
+}
+
+impl CompiledTemplate {
+    pub(crate) fn compile(template: &str) -> Result<Self, TemplateError> {
+        let error = |message: String| TemplateError {
+            template: template.to_string(),
+            message,
+        };
+
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '}' => return Err(error("unmatched '}', use '}}' for a literal brace".to_string())),
+                '{' => {
+                    let mut placeholder = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => placeholder.push(c),
+                            None => return Err(error("unclosed '{'".to_string())),
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(Self::compile_placeholder(placeholder.as_str()).map_err(error)?);
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Self {
+            segments,
+        })
+    }
+
+    fn compile_placeholder(placeholder: &str) -> Result<Segment, String> {
+        let (name, spec) = match placeholder.split_once(':') {
+            Some((name, spec)) => (name.trim(), Some(spec)),
+            None => (placeholder.trim(), None),
+        };
+        if name == "ts" || name == "timestamp" {
+            let time_format = spec.unwrap_or(DEFAULT_TIME_FORMAT);
+            // Without chrono the format is checked once logging is compiled in.
+            #[cfg(feature = "_log")]
+            if StrftimeItems::new(time_format).any(|item| matches!(item, Item::Error)) {
+                return Err(format!("invalid time format \"{}\"", time_format));
+            }
+            return Ok(Segment::Timestamp(time_format.to_string()));
+        }
+        let field = match name {
+            "level" => Field::Level,
+            "target" => Field::Target,
+            "module" => Field::Module,
+            "file" => Field::File,
+            "path" => Field::Path,
+            "line" => Field::Line,
+            "thread" => Field::Thread,
+            "thread_id" => Field::ThreadId,
+            "pid" => Field::Pid,
+            "app_name" => Field::AppName,
+            "instance_id" => Field::InstanceId,
+            "msg" | "message" => Field::Message,
+            _ => return Err(format!("unknown placeholder \"{{{}}}\"", name)),
+        };
+        let padding = match spec {
+            Some(spec) => Some(parse_padding(spec)?),
+            None => None,
+        };
+        Ok(Segment::Field(field, padding))
+    }
+}
+
+fn parse_padding(spec: &str) -> Result<Padding, String> {
+    let (align, width) = match spec.chars().next() {
+        Some('<') => (Align::Left, &spec[1..]),
+        Some('>') => (Align::Right, &spec[1..]),
+        Some('^') => (Align::Center, &spec[1..]),
+        _ => (Align::Left, spec),
+    };
+    let width = width.parse().map_err(|_| format!("invalid padding \"{}\", expected e.g. <10, >5 or ^8", spec))?;
+    Ok(Padding {
+        align,
+        width,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile_error(template: &str) -> String {
+        CompiledTemplate::compile(template).unwrap_err().message
+    }
+
+    #[test]
+    fn doubled_braces_are_literals() {
+        let template = CompiledTemplate::compile("{{level}} }}{level}{{").unwrap();
+        assert!(matches!(template.segments.as_slice(), [
+            Segment::Literal(before),
+            Segment::Field(Field::Level, None),
+            Segment::Literal(after),
+        ] if before == "{level} }" && after == "{"));
+    }
+
+    #[test]
+    fn rejects_unbalanced_braces() {
+        assert_eq!(compile_error("{level"), "unclosed '{'");
+        assert_eq!(compile_error("{msg} }"), "unmatched '}', use '}}' for a literal brace");
+    }
+
+    #[test]
+    fn rejects_unknown_placeholders() {
+        assert_eq!(compile_error("{lvl}"), "unknown placeholder \"{lvl}\"");
+        assert_eq!(compile_error("{}"), "unknown placeholder \"{}\"");
+    }
+
+    #[test]
+    fn parses_timestamps() {
+        let template = CompiledTemplate::compile("{ts} {ts:%H:%M:%S%.3f}").unwrap();
+        assert!(matches!(template.segments.as_slice(), [
+            Segment::Timestamp(default),
+            Segment::Literal(_),
+            Segment::Timestamp(custom),
+        ] if default == DEFAULT_TIME_FORMAT && custom == "%H:%M:%S%.3f"));
+    }
+
+    #[cfg(feature = "_log")]
+    #[test]
+    fn rejects_bad_time_formats() {
+        assert_eq!(compile_error("{ts:%Q}"), "invalid time format \"%Q\"");
+        assert!(LogFormat::Template("{ts:%Y-%}".to_string()).validate().is_err());
+    }
+
+    #[test]
+    fn parses_padding() {
+        assert!(matches!(parse_padding(">5"), Ok(Padding { align: Align::Right, width: 5 })));
+        assert!(matches!(parse_padding("<20"), Ok(Padding { align: Align::Left, width: 20 })));
+        assert!(matches!(parse_padding("^8"), Ok(Padding { align: Align::Center, width: 8 })));
+        assert!(matches!(parse_padding("7"), Ok(Padding { align: Align::Left, width: 7 })));
+        assert_eq!(compile_error("{level:>x}"), "invalid padding \">x\", expected e.g. <10, >5 or ^8");
+        assert!(parse_padding("").is_err());
+    }
+
+    #[cfg(sfo_log_backend)]
+    #[test]
+    fn pads_fields() {
+        use flexi_logger::DeferredNow;
+        use tracing::log;
+        use crate::line_format::LineFormatter;
+
+        let format = LogFormat::Template("[{level:>5}|{target:<6}|{app_name:^7}] {msg}".to_string());
+        let formatter = LineFormatter::new(&format, "app", "").unwrap();
+        let mut line = Vec::new();
+        formatter.write(&mut line, &mut DeferredNow::new(), &log::Record::builder()
+            .level(log::Level::Info)
+            .target("net")
+            .args(format_args!("hello"))
+            .build()).unwrap();
+        assert_eq!(String::from_utf8(line).unwrap(), "[ INFO|net   |  app  ] hello");
+    }
+}
@@ -0,0 +1,123 @@
+#[cfg(sfo_log_backend)]
+use std::sync::{Arc, RwLock};
+use crate::level::parse_directives;
+use crate::{Level, RecentRecord, SfoLogError};
+#[cfg(sfo_log_backend)]
+use crate::sfo_logger::{read_state, SfoLogState};
+#[cfg(sfo_log_backend)]
+use crate::periodic::PeriodicThread;
+#[cfg(sfo_log_backend)]
+use crate::async_writer::AsyncWriter;
+
+/// Keeps the loggers created by [`crate::Logger::start`] alive.
+///
+/// Buffered output of the main log and of every module log is flushed when the handle is dropped,
+/// so it should be held until the end of the program.
+#[must_use = "dropping the handle flushes and shuts down the file loggers"]
+pub struct SfoLogHandle {
+    #[cfg(sfo_log_backend)]
+    state: Arc<RwLock<SfoLogState>>,
+    #[cfg(sfo_log_backend)]
+    background_threads: Vec<PeriodicThread>,
+    #[cfg(sfo_log_backend)]
+    async_writer: Option<AsyncWriter>,
+}
+
+impl SfoLogHandle {
+    #[cfg(sfo_log_backend)]
+    pub(crate) fn new(state: Arc<RwLock<SfoLogState>>, background_threads: Vec<PeriodicThread>, async_writer: Option<AsyncWriter>) -> Self {
+        Self {
+            state,
+            background_threads,
+            async_writer,
+        }
+    }
+
+    #[cfg(not(sfo_log_backend))]
+    pub(crate) fn new() -> Self {
+        Self {}
+    }
+
+    /// Replaces the level spec of the main log, e.g. `"info"` or `"info,my_crate::net=debug"`.
+    pub fn set_log_level(&self, level: &str) -> Result<(), SfoLogError> {
+        let directives = parse_directives(level)?;
+        #[cfg(sfo_log_backend)]
+        read_state(&self.state).set_main_levels(directives);
+        #[cfg(not(sfo_log_backend))]
+        let _ = directives;
+        Ok(())
+    }
+
+    /// Replaces the level spec of the module log registered with `module_key` in [`crate::Logger::add_module_log`].
+    ///
+    /// Returns `false` if no such module log exists.
+    pub fn set_module_log_level(&self, module_key: &str, level: &str) -> Result<bool, SfoLogError> {
+        let directives = parse_directives(level)?;
+        #[cfg(sfo_log_backend)]
+        {
+            Ok(read_state(&self.state).set_module_levels(module_key, directives))
+        }
+        #[cfg(not(sfo_log_backend))]
+        {
+            let _ = (module_key, directives);
+            Ok(false)
+        }
+    }
+
+    /// Number of records discarded because the queue set with [`crate::Logger::set_async`] was full.
+    pub fn dropped_records(&self) -> u64 {
+        #[cfg(sfo_log_backend)]
+        {
+            self.async_writer.as_ref().map(|v| v.queue().dropped_records()).unwrap_or(0)
+        }
+        #[cfg(not(sfo_log_backend))]
+        0
+    }
+
+    /// The newest `limit` records kept by [`crate::Logger::set_recent_buffer`] at `level_min` or more severe
+    /// whose target starts with the `::` segments of `target_prefix`, oldest first; an empty prefix matches every target.
+    ///
+    /// Returns nothing without a recent buffer.
+    pub fn recent(&self, level_min: Level, target_prefix: &str, limit: usize) -> Vec<RecentRecord> {
+        #[cfg(sfo_log_backend)]
+        {
+            match read_state(&self.state).recent.as_ref() {
+                Some(recent) => recent.query(level_min, target_prefix, limit),
+                None => Vec::new(),
+            }
+        }
+        #[cfg(not(sfo_log_backend))]
+        {
+            let _ = (level_min, target_prefix, limit);
+            Vec::new()
+        }
+    }
+
+    /// Waits for the queued records to be written, then flushes every log.
+    pub fn flush(&self) {
+        #[cfg(sfo_log_backend)]
+        {
+            if let Some(async_writer) = self.async_writer.as_ref() {
+                async_writer.queue().wait_drained();
+            }
+            read_state(&self.state).flush();
+        }
+    }
+
+    #[cfg_attr(not(sfo_log_backend), allow(unused_mut))]
+    pub fn shutdown(mut self) {
+        #[cfg(sfo_log_backend)]
+        {
+            self.background_threads.clear();
+            // Writes the records still queued; later records are written on the calling thread.
+            self.async_writer = None;
+            read_state(&self.state).shutdown();
+        }
+    }
+}
+
+impl Drop for SfoLogHandle {
+    fn drop(&mut self) {
+        self.flush();
+    }
+}
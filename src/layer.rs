@@ -0,0 +1,242 @@
+use std::cell::RefCell;
+use std::fmt::{self, Write};
+use tracing::field::{Field, Visit};
+use tracing::level_filters::{LevelFilter, STATIC_MAX_LEVEL};
+use tracing::span::{Attributes, Record as SpanRecord};
+use tracing::subscriber::{Interest, SetGlobalDefaultError};
+use tracing::{log, Event, Id, Level, Metadata, Subscriber};
+use tracing_subscriber::layer::{Context, SubscriberExt};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+use crate::fields::{FieldValue, Fields};
+use crate::sfo_logger::installed_logger;
+
+// Span lifecycle records tracing emits through `log`; the layer renders spans in the event line instead.
+const SPAN_LOG_TARGETS: [&str; 2] = ["tracing::span", "tracing::span::active"];
+
+// The event or span the layer enabled last on this thread. tracing sends the `log` copy right after
+// asking `enabled`, before or after dispatching depending on its version, so the copy matches this once.
+#[derive(Default)]
+struct PendingEvent {
+    active: bool,
+    target: String,
+    file: String,
+    line: Option<u32>,
+}
+
+thread_local! {
+    static PENDING_EVENT: RefCell<PendingEvent> = RefCell::new(PendingEvent::default());
+}
+
+/// A `tracing_subscriber` layer writing events through the loggers started by [`crate::Logger::start`].
+///
+/// Lines are prefixed with the span stack of the event, e.g.
+/// `conn{peer=10.0.0.1:80}:request{id=7}: sent bytes=512`; the event fields are passed on as
+/// key/value pairs, so [`crate::LogFormat::Json`] writes them with their types. Events the layer writes
+/// skip the copy tracing also sends to `log`; on threads whose dispatcher lacks the layer that copy is
+/// written instead.
+///
+/// ```no_run
+/// let _handle = sfo_log::Logger::new("my_app").start().unwrap();
+/// sfo_log::SfoLogLayer::install().unwrap();
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SfoLogLayer {}
+
+impl SfoLogLayer {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    /// Sets a registry with only this layer as the global `tracing` subscriber.
+    pub fn install() -> Result<(), SetGlobalDefaultError> {
+        tracing::subscriber::set_global_default(tracing_subscriber::registry().with(Self::new()))
+    }
+}
+
+// Fields of a span rendered as `a=1 b=2`, stored in the span's extensions.
+struct SpanFields(String);
+
+struct FieldWriter<'a> {
+    output: &'a mut String,
+}
+
+impl FieldWriter<'_> {
+    fn push(&mut self, name: &str, value: fmt::Arguments) {
+        if !self.output.is_empty() {
+            self.output.push(' ');
+        }
+        let _ = write!(self.output, "{}={}", name, value);
+    }
+}
+
+impl Visit for FieldWriter<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field.name(), format_args!("{}", value));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.push(field.name(), format_args!("{:?}", value));
+    }
+}
+
+// The message of an event and its other fields as key/value pairs for the record.
+#[derive(Default)]
+struct EventFields {
+    message: String,
+    fields: Fields,
+}
+
+impl EventFields {
+    fn push(&mut self, field: &Field, value: FieldValue) {
+        self.fields.push((field.name().to_string(), value));
+    }
+}
+
+impl Visit for EventFields {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, FieldValue::F64(value));
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, FieldValue::I64(value));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.push(field, FieldValue::U64(value));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, FieldValue::Bool(value));
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.push(field, FieldValue::Str(value.to_string()));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            let _ = write!(self.message, "{:?}", value);
+        } else {
+            self.push(field, FieldValue::Str(format!("{:?}", value)));
+        }
+    }
+}
+
+pub(crate) fn log_level(level: &Level) -> log::Level {
+    match *level {
+        Level::ERROR => log::Level::Error,
+        Level::WARN => log::Level::Warn,
+        Level::INFO => log::Level::Info,
+        Level::DEBUG => log::Level::Debug,
+        Level::TRACE => log::Level::Trace,
+    }
+}
+
+impl<S> Layer<S> for SfoLogLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn register_callsite(&self, _metadata: &'static Metadata<'static>) -> Interest {
+        // The log level can change at runtime, so `enabled` is asked for every event.
+        Interest::sometimes()
+    }
+
+    // Lets tracing skip callsites above the `max_level_*` features without asking the layer.
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        Some(STATIC_MAX_LEVEL)
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        let enabled = metadata.is_span() || log_level(metadata.level()) <= log::max_level();
+        if enabled {
+            PENDING_EVENT.with_borrow_mut(|pending| {
+                pending.active = true;
+                pending.target.clear();
+                pending.target.push_str(metadata.target());
+                pending.file.clear();
+                pending.file.push_str(metadata.file().unwrap_or_default());
+                pending.line = metadata.line();
+            });
+        }
+        enabled
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut fields = String::new();
+        attrs.record(&mut FieldWriter {
+            output: &mut fields,
+        });
+        span.extensions_mut().insert(SpanFields(fields));
+    }
+
+    fn on_record(&self, id: &Id, values: &SpanRecord<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<SpanFields>() {
+            values.record(&mut FieldWriter {
+                output: &mut fields.0,
+            });
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let Some(logger) = installed_logger() else {
+            return;
+        };
+        let metadata = event.metadata();
+
+        let mut line = String::new();
+        if let Some(scope) = ctx.event_scope(event) {
+            for span in scope.from_root() {
+                line.push_str(span.name());
+                if let Some(fields) = span.extensions().get::<SpanFields>() && !fields.0.is_empty() {
+                    let _ = write!(line, "{{{}}}", fields.0);
+                }
+                line.push(':');
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+        }
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+        line.push_str(fields.message.as_str());
+        if fields.message.is_empty() {
+            // The fields follow the span stack directly.
+            line.truncate(line.trim_end().len());
+        }
+
+        logger.write(&log::Record::builder()
+            .args(format_args!("{}", line))
+            .level(log_level(metadata.level()))
+            .target(metadata.target())
+            .module_path(metadata.module_path())
+            .file(metadata.file())
+            .line(metadata.line())
+            .key_values(&fields.fields)
+            .build());
+    }
+}
+
+/// Whether `record` is the `log` copy of a tracing event or span; the layer writes those itself.
+pub(crate) fn is_layer_record(record: &log::Record) -> bool {
+    if SPAN_LOG_TARGETS.contains(&record.target()) {
+        return tracing::dispatcher::get_default(|dispatch| dispatch.is::<SfoLogLayer>());
+    }
+    PENDING_EVENT.with_borrow_mut(|pending| {
+        let matches = pending.active
+            && pending.line == record.line()
+            && pending.target == record.target()
+            && pending.file == record.file().unwrap_or_default();
+        if matches {
+            pending.active = false;
+        }
+        matches
+    })
+}
@@ -0,0 +1,189 @@
+use std::fmt;
+use crate::route_table::RouteTable;
+use crate::{Level, SfoLogError};
+
+/// A level spec such as `info,my_app::p2p=trace,hyper=off`.
+///
+/// Each comma separated part is either the default level or `target=level`; a target alone
+/// enables all its levels. Targets match like module log keys: on whole `::` segments, with `*`
+/// matching any one segment, and the target with the most matching segments decides.
+/// Without a default level, records of other targets are dropped.
+#[derive(Debug, Clone, Default)]
+pub struct LevelDirectives {
+    default: Option<Level>,
+    directives: Vec<(String, Option<Level>)>,
+    table: RouteTable,
+}
+
+impl LevelDirectives {
+    /// Directives enabling `level` and everything more severe for every target.
+    pub fn new(level: Level) -> Self {
+        Self {
+            default: Some(level),
+            ..Self::default()
+        }
+    }
+
+    /// Directives dropping every record.
+    pub fn off() -> Self {
+        Self::default()
+    }
+
+    /// Sets the most verbose level of `target`, `None` turns it off.
+    pub fn add(mut self, target: &str, level: Option<Level>) -> Self {
+        match self.directives.iter_mut().find(|(v, _)| v == target) {
+            Some(directive) => directive.1 = level,
+            None => self.directives.push((target.to_string(), level)),
+        }
+        self.table = RouteTable::new(self.directives.iter().map(|(target, _)| target.as_str()));
+        self
+    }
+
+    /// The most verbose level enabled for `target`, `None` if it is off.
+    pub fn level_for(&self, target: &str) -> Option<Level> {
+        match self.table.find(target) {
+            Some(index) => self.directives[index].1,
+            None => self.default,
+        }
+    }
+
+    /// The most verbose level enabled for any target.
+    pub fn max_level(&self) -> Option<Level> {
+        self.directives.iter().map(|(_, level)| *level).fold(self.default, |max, level| max.max(level))
+    }
+}
+
+/// Parses `spec`, reporting failures as [`SfoLogError::InvalidLevel`].
+pub(crate) fn parse_directives(spec: &str) -> Result<LevelDirectives, SfoLogError> {
+    spec.parse().map_err(|message: String| SfoLogError::InvalidLevel {
+        spec: spec.to_string(),
+        source: message.into(),
+    })
+}
+
+pub(crate) fn parse_level(level: &str) -> Result<Option<Level>, String> {
+    match level.trim().to_ascii_lowercase().as_str() {
+        "off" => Ok(None),
+        "error" => Ok(Some(Level::ERROR)),
+        "warn" => Ok(Some(Level::WARN)),
+        "info" => Ok(Some(Level::INFO)),
+        "debug" => Ok(Some(Level::DEBUG)),
+        "trace" => Ok(Some(Level::TRACE)),
+        _ => Err(format!("unknown level \"{}\", expected off, error, warn, info, debug or trace", level.trim())),
+    }
+}
+
+fn level_name(level: Option<Level>) -> &'static str {
+    match level {
+        None => "off",
+        Some(Level::ERROR) => "error",
+        Some(Level::WARN) => "warn",
+        Some(Level::INFO) => "info",
+        Some(Level::DEBUG) => "debug",
+        Some(_) => "trace",
+    }
+}
+
+impl std::str::FromStr for LevelDirectives {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut directives = LevelDirectives::off();
+        for part in s.split(',').map(|v| v.trim()).filter(|v| !v.is_empty()) {
+            if part.contains('/') {
+                return Err(format!("text filters are not supported in \"{}\", use a filter rule instead", part));
+            }
+            directives = match part.split_once('=') {
+                Some((target, level)) if !target.trim().is_empty() => directives.add(target.trim(), parse_level(level)?),
+                Some(_) => return Err(format!("missing target in \"{}\"", part)),
+                None => match parse_level(part) {
+                    Ok(level) => {
+                        directives.default = level;
+                        directives
+                    }
+                    Err(_) => directives.add(part, Some(Level::TRACE)),
+                },
+            };
+        }
+        Ok(directives)
+    }
+}
+
+impl fmt::Display for LevelDirectives {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", level_name(self.default))?;
+        for (target, level) in self.directives.iter() {
+            write!(f, ",{}={}", target, level_name(*level))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RouteMode;
+
+    #[test]
+    fn parses_default_and_targets() {
+        let directives: LevelDirectives = "info,my_app::net=debug,hyper=off,my_app::*::rpc=trace".parse().unwrap();
+        assert_eq!(directives.level_for("my_app"), Some(Level::INFO));
+        assert_eq!(directives.level_for("my_app::net::tcp"), Some(Level::DEBUG));
+        assert_eq!(directives.level_for("my_app::network"), Some(Level::INFO));
+        assert_eq!(directives.level_for("hyper::client"), None);
+        assert_eq!(directives.level_for("my_app::db::rpc"), Some(Level::TRACE));
+        assert_eq!(directives.level_for("my_app::net::rpc"), Some(Level::TRACE));
+        assert_eq!(directives.max_level(), Some(Level::TRACE));
+    }
+
+    #[test]
+    fn bare_target_enables_trace_and_drops_others() {
+        let directives: LevelDirectives = "my_app".parse().unwrap();
+        assert_eq!(directives.level_for("my_app::net"), Some(Level::TRACE));
+        assert_eq!(directives.level_for("hyper"), None);
+    }
+
+    #[test]
+    fn off_everywhere() {
+        let directives: LevelDirectives = "off,my_app=off".parse().unwrap();
+        assert_eq!(directives.level_for("my_app"), None);
+        assert_eq!(directives.level_for("hyper"), None);
+        assert_eq!(directives.max_level(), None);
+        assert_eq!(LevelDirectives::off().max_level(), None);
+    }
+
+    #[test]
+    fn later_directive_for_a_target_replaces_earlier() {
+        let directives = LevelDirectives::new(Level::WARN)
+            .add("my_app", Some(Level::DEBUG))
+            .add("my_app", None);
+        assert_eq!(directives.level_for("my_app"), None);
+        assert_eq!(directives.max_level(), Some(Level::WARN));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        for spec in ["info", "off,my_app=debug", "warn,hyper=off,my_app::*::rpc=trace"] {
+            let directives: LevelDirectives = spec.parse().unwrap();
+            assert_eq!(directives.to_string(), spec);
+            assert_eq!(directives.to_string().parse::<LevelDirectives>().unwrap().to_string(), spec);
+        }
+        assert_eq!(" INFO , my_app = Debug ".parse::<LevelDirectives>().unwrap().to_string(), "info,my_app=debug");
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        assert!("my_app=loud".parse::<LevelDirectives>().is_err());
+        assert!("=info".parse::<LevelDirectives>().is_err());
+        assert!("info/heartbeat".parse::<LevelDirectives>().is_err());
+        assert!(matches!(parse_directives("my_app=loud"), Err(SfoLogError::InvalidLevel { .. })));
+    }
+
+    #[test]
+    fn route_modes_share_the_level_parser() {
+        assert_eq!("main_above:WARN".parse::<RouteMode>(), Ok(RouteMode::MainAbove(Level::WARN)));
+        assert_eq!("exclusive".parse::<RouteMode>(), Ok(RouteMode::Exclusive));
+        assert_eq!("main_above:loud".parse::<RouteMode>(), Err(parse_level("loud").unwrap_err()));
+        assert!("main_above:off".parse::<RouteMode>().is_err());
+    }
+}
@@ -1,644 +1,118 @@
-use std::collections::HashSet;
-use std::path::{Path, PathBuf};
-use std::thread;
-#[cfg(feature = "_log")]
-use flexi_logger::{Cleanup, Criterion, DeferredNow, Duplicate, FileSpec, FlexiLoggerError, Naming, Record};
-use flexi_logger::filter::{LogLineFilter, LogLineWriter};
-#[cfg(all(feature = "_log", not(feature = "nolog")))]
+use std::ops::RangeBounds;
+#[cfg(any(sfo_log_backend, feature = "config"))]
+use std::path::Path;
+use std::path::PathBuf;
+#[cfg(sfo_log_backend)]
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+#[cfg(sfo_log_backend)]
+use flexi_logger::{Cleanup, Duplicate, FileSpec, FlexiLoggerError, LogSpecification, LoggerHandle};
+#[cfg(sfo_log_backend)]
 pub use tracing::{info, warn, trace, debug, error};
+#[cfg(sfo_log_backend)]
+pub use tracing::{event, span, trace_span, debug_span, info_span, warn_span, error_span, Instrument, Level, Span};
+pub use sfo_log_macros::instrument;
+#[cfg(sfo_log_backend)]
 use tracing::log;
-use tracing::log::Metadata;
-
-#[cfg(feature = "nolog")]
-#[macro_export]
-macro_rules! error {
-    // Name / target / parent.
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name / target.
-    (name: $name:expr, target: $target:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, $($arg:tt)+ ) => (
-    );
-
-    // Target / parent.
-    (target: $target:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name / parent.
-    (name: $name:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name.
-    (name: $name:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, $($arg:tt)+ ) => (
-    );
-
-    // Target.
-    (target: $target:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (target: $target:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, $($arg:tt)+ ) => (
-    );
-
-    // Parent.
-    (parent: $parent:expr, { $($field:tt)+ }, $($arg:tt)+ ) => (
-    );
-    (parent: $parent:expr, $($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, ?$($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, %$($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, $($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, ?$($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, %$($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, $($arg:tt)+) => (
-    );
-
-    // ...
-    ({ $($field:tt)+ }, $($arg:tt)+ ) => (
-    );
-    ($($k:ident).+ = $($field:tt)*) => (
-    );
-    (?$($k:ident).+ = $($field:tt)*) => (
-    );
-    (%$($k:ident).+ = $($field:tt)*) => (
-    );
-    ($($k:ident).+, $($field:tt)*) => (
-    );
-    (?$($k:ident).+, $($field:tt)*) => (
-    );
-    (%$($k:ident).+, $($field:tt)*) => (
-    );
-    (?$($k:ident).+) => (
-    );
-    (%$($k:ident).+) => (
-    );
-    ($($k:ident).+) => (
-    );
-    ($($arg:tt)+) => (
-    );
-}
-
-#[cfg(feature = "nolog")]
-#[macro_export]
-macro_rules! debug {
-    // Name / target / parent.
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name / target.
-    (name: $name:expr, target: $target:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, $($arg:tt)+ ) => (
-    );
-
-    // Target / parent.
-    (target: $target:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name / parent.
-    (name: $name:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name.
-    (name: $name:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, $($arg:tt)+ ) => (
-    );
-
-    // Target.
-    (target: $target:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (target: $target:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, $($arg:tt)+ ) => (
-    );
-
-    // Parent.
-    (parent: $parent:expr, { $($field:tt)+ }, $($arg:tt)+ ) => (
-    );
-    (parent: $parent:expr, $($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, ?$($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, %$($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, $($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, ?$($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, %$($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, $($arg:tt)+) => (
-    );
-
-    // ...
-    ({ $($field:tt)+ }, $($arg:tt)+ ) => (
-    );
-    ($($k:ident).+ = $($field:tt)*) => (
-    );
-    (?$($k:ident).+ = $($field:tt)*) => (
-    );
-    (%$($k:ident).+ = $($field:tt)*) => (
-    );
-    ($($k:ident).+, $($field:tt)*) => (
-    );
-    (?$($k:ident).+, $($field:tt)*) => (
-    );
-    (%$($k:ident).+, $($field:tt)*) => (
-    );
-    (?$($k:ident).+) => (
-    );
-    (%$($k:ident).+) => (
-    );
-    ($($k:ident).+) => (
-    );
-    ($($arg:tt)+) => (
-    );
-}
-
-#[cfg(feature = "nolog")]
-#[macro_export]
-macro_rules! trace {
-    // Name / target / parent.
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name / target.
-    (name: $name:expr, target: $target:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, $($arg:tt)+ ) => (
-    );
-
-    // Target / parent.
-    (target: $target:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name / parent.
-    (name: $name:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name.
-    (name: $name:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, $($arg:tt)+ ) => (
-    );
-
-    // Target.
-    (target: $target:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (target: $target:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, $($arg:tt)+ ) => (
-    );
-
-    // Parent.
-    (parent: $parent:expr, { $($field:tt)+ }, $($arg:tt)+ ) => (
-    );
-    (parent: $parent:expr, $($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, ?$($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, %$($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, $($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, ?$($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, %$($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, $($arg:tt)+) => (
-    );
-
-    // ...
-    ({ $($field:tt)+ }, $($arg:tt)+ ) => (
-    );
-    ($($k:ident).+ = $($field:tt)*) => (
-    );
-    (?$($k:ident).+ = $($field:tt)*) => (
-    );
-    (%$($k:ident).+ = $($field:tt)*) => (
-    );
-    ($($k:ident).+, $($field:tt)*) => (
-    );
-    (?$($k:ident).+, $($field:tt)*) => (
-    );
-    (%$($k:ident).+, $($field:tt)*) => (
-    );
-    (?$($k:ident).+) => (
-    );
-    (%$($k:ident).+) => (
-    );
-    ($($k:ident).+) => (
-    );
-    ($($arg:tt)+) => (
-    );
-}
-
-#[cfg(feature = "nolog")]
-#[macro_export]
-macro_rules! info {
-    // Name / target / parent.
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name / target.
-    (name: $name:expr, target: $target:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, $($arg:tt)+ ) => (
-    );
-
-    // Target / parent.
-    (target: $target:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name / parent.
-    (name: $name:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name.
-    (name: $name:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, $($arg:tt)+ ) => (
-    );
-
-    // Target.
-    (target: $target:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (target: $target:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, $($arg:tt)+ ) => (
-    );
-
-    // Parent.
-    (parent: $parent:expr, { $($field:tt)+ }, $($arg:tt)+ ) => (
-    );
-    (parent: $parent:expr, $($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, ?$($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, %$($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, $($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, ?$($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, %$($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, $($arg:tt)+) => (
-    );
-
-    // ...
-    ({ $($field:tt)+ }, $($arg:tt)+ ) => (
-    );
-    ($($k:ident).+ = $($field:tt)*) => (
-    );
-    (?$($k:ident).+ = $($field:tt)*) => (
-    );
-    (%$($k:ident).+ = $($field:tt)*) => (
-    );
-    ($($k:ident).+, $($field:tt)*) => (
-    );
-    (?$($k:ident).+, $($field:tt)*) => (
-    );
-    (%$($k:ident).+, $($field:tt)*) => (
-    );
-    (?$($k:ident).+) => (
-    );
-    (%$($k:ident).+) => (
-    );
-    ($($k:ident).+) => (
-    );
-    ($($arg:tt)+) => (
-    );
-}
-
-
-#[cfg(feature = "nolog")]
-#[macro_export]
-macro_rules! warn {
-    // Name / target / parent.
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name / target.
-    (name: $name:expr, target: $target:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, target: $target:expr, $($arg:tt)+ ) => (
-    );
-
-    // Target / parent.
-    (target: $target:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name / parent.
-    (name: $name:expr, parent: $parent:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, parent: $parent:expr, $($arg:tt)+ ) => (
-    );
-
-    // Name.
-    (name: $name:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (name: $name:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (name: $name:expr, $($arg:tt)+ ) => (
-    );
-
-    // Target.
-    (target: $target:expr, { $($field:tt)* }, $($arg:tt)* ) => (
-    );
-    (target: $target:expr, $($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, ?$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, %$($k:ident).+ $($field:tt)* ) => (
-    );
-    (target: $target:expr, $($arg:tt)+ ) => (
-    );
-
-    // Parent.
-    (parent: $parent:expr, { $($field:tt)+ }, $($arg:tt)+ ) => (
-    );
-    (parent: $parent:expr, $($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, ?$($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, %$($k:ident).+ = $($field:tt)*) => (
-    );
-    (parent: $parent:expr, $($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, ?$($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, %$($k:ident).+, $($field:tt)*) => (
-    );
-    (parent: $parent:expr, $($arg:tt)+) => (
-    );
-
-    // ...
-    ({ $($field:tt)+ }, $($arg:tt)+ ) => (
-    );
-    ($($k:ident).+ = $($field:tt)*) => (
-    );
-    (?$($k:ident).+ = $($field:tt)*) => (
-    );
-    (%$($k:ident).+ = $($field:tt)*) => (
-    );
-    ($($k:ident).+, $($field:tt)*) => (
-    );
-    (?$($k:ident).+, $($field:tt)*) => (
-    );
-    (%$($k:ident).+, $($field:tt)*) => (
-    );
-    (?$($k:ident).+) => (
-    );
-    (%$($k:ident).+) => (
-    );
-    ($($k:ident).+) => (
-    );
-    ($($arg:tt)+) => (
-    );
-}
-
 
-#[cfg(feature = "_log")]
-fn custom_format(writer: &mut dyn std::io::Write, now: &mut DeferredNow, record: &Record) -> std::io::Result<()> {
-    let file = match record.file() {
-        None => {
-            "<unknown>".to_string()
-        }
-        Some(path) => {
-            Path::new(path).file_name().map(|v| v.to_string_lossy().to_string()).unwrap_or("<unknown>".to_string())
-        }
-    };
-    let module = if let Some((first, _)) = record.metadata().target().split_once("::") {
-        first
-    } else {
-        record.metadata().target()
-    };
-    write!(
-        writer,
-        "{} [{}] [{}:{}:{}] [{}] - {}",
-        now.format("%Y-%m-%d %H:%M:%S"),
-        record.level(),
-        module,
-        file,
-        record.line().unwrap_or(0),
-        thread::current().name().unwrap_or(format!("{:?}", thread::current().id()).as_str()),
-        &record.args()
-    )
-}
+#[cfg(sfo_log_backend)]
+mod async_writer;
+mod config;
+mod error;
+#[cfg(sfo_log_backend)]
+mod fields;
+mod filter;
+mod format;
+mod handle;
+mod level;
+#[cfg(sfo_log_backend)]
+mod layer;
+#[cfg(sfo_log_backend)]
+mod line_format;
+// Without a logging backend, either through `nolog` or without the default `_log` feature,
+// the macros, spans and the tracing layer are replaced by no-op stand-ins.
+#[cfg(not(sfo_log_backend))]
+mod nolog;
+mod overflow;
+#[cfg(sfo_log_backend)]
+mod periodic;
+mod recent;
+#[cfg(sfo_log_backend)]
+mod retention;
+mod rotation;
+mod route;
+mod route_table;
+#[cfg(sfo_log_backend)]
+mod sfo_logger;
+#[cfg(all(sfo_log_backend, feature = "config"))]
+mod watch;
+
+#[cfg(sfo_log_backend)]
+use async_writer::AsyncWriter;
+pub use config::{LoggerConfig, LoggerConfigError, ModuleLogConfig, PredicateLogConfig};
+pub use error::SfoLogError;
+pub use filter::{FilterAction, FilterRule};
+#[cfg(sfo_log_backend)]
+use filter::CompiledFilter;
+pub use format::{LogFormat, TemplateError};
+pub use handle::SfoLogHandle;
+pub use level::LevelDirectives;
+#[cfg(sfo_log_backend)]
+use level::parse_directives;
+#[cfg(sfo_log_backend)]
+pub use layer::SfoLogLayer;
+#[cfg(sfo_log_backend)]
+use line_format::{preformatted, LineFormatter};
+#[cfg(not(sfo_log_backend))]
+pub use nolog::{Entered, EnteredSpan, Instrument, Level, SfoLogLayer, Span};
+#[cfg(not(sfo_log_backend))]
+#[doc(hidden)]
+pub use nolog::{__parent, __Parent};
+pub use overflow::OverflowPolicy;
+pub use recent::{RecentLimit, RecentRecord};
+pub use rotation::{FileNaming, RotationPolicy};
+pub use route::{LogPredicate, ModuleLogOptions, RouteMode};
+use route::{ModuleRoute, PredicateRoute};
+#[cfg(sfo_log_backend)]
+use route::CompiledPredicate;
+#[cfg(sfo_log_backend)]
+use sfo_logger::{install_logger, read_state, ModuleLogger, PredicateLogger, SfoLogFilter, SfoLogState, SfoLogger};
+#[cfg(sfo_log_backend)]
+use recent::RecentBuffer;
+#[cfg(sfo_log_backend)]
+use retention::{sweep_retention, Retention, RetentionGroup};
+#[cfg(sfo_log_backend)]
+use route_table::RouteTable;
+#[cfg(all(sfo_log_backend, feature = "config"))]
+use watch::watch_config;
+
+#[derive(Clone)]
+// Without a logging backend the settings are kept but never read.
+#[cfg_attr(not(sfo_log_backend), allow(dead_code))]
 pub struct Logger {
     app_name: String,
     log_level: String,
     log_to_file: bool,
-    log_path: PathBuf,
+    log_path: Option<PathBuf>,
     log_file_size: u64,
     log_file_count: usize,
+    rotation: RotationPolicy,
+    file_naming: FileNaming,
+    compress_rotated_files: bool,
+    log_max_age: Option<Duration>,
+    log_max_total_size: Option<u64>,
     instance_id: String,
     output_console: bool,
-    filter: Vec<String>,
-    module_logs: Vec<(String, String)>,
+    format: LogFormat,
+    async_queue_size: Option<usize>,
+    overflow_policy: OverflowPolicy,
+    recent_limit: Option<RecentLimit>,
+    filter: Vec<FilterRule>,
+    module_logs: Vec<ModuleRoute>,
+    predicate_logs: Vec<PredicateRoute>,
+    #[cfg(feature = "config")]
+    config_watch: Option<PathBuf>,
+    env_config: Option<LoggerConfig>,
 }
 
 impl Logger {
@@ -647,13 +121,26 @@ impl Logger {
             app_name: app_name.to_string(),
             log_level: "info".to_string(),
             log_to_file: false,
-            log_path: std::env::current_dir().unwrap().join("logs"),
+            log_path: None,
             log_file_size: 10 * 1024 * 1024,
             log_file_count: 10,
+            rotation: RotationPolicy::Size,
+            file_naming: FileNaming::Numbers,
+            compress_rotated_files: false,
+            log_max_age: None,
+            log_max_total_size: None,
             instance_id: "".to_string(),
             output_console: true,
+            format: LogFormat::Text,
+            async_queue_size: None,
+            overflow_policy: OverflowPolicy::Block,
+            recent_limit: None,
             filter: vec![],
             module_logs: vec![],
+            predicate_logs: vec![],
+            #[cfg(feature = "config")]
+            config_watch: None,
+            env_config: None,
         }
     }
 
@@ -667,18 +154,29 @@ impl Logger {
         self
     }
 
+    /// Level spec of the main log and of the module logs without their own, e.g. `"info,my_app::p2p=trace"`.
+    ///
+    /// `RUST_LOG` takes precedence when set; the spec is parsed into [`LevelDirectives`] by [`Logger::start`].
     pub fn set_log_level(mut self, level: &str) -> Self {
         self.log_level = level.to_string();
         self
     }
 
+    pub fn set_level_directives(self, directives: &LevelDirectives) -> Self {
+        self.set_log_level(directives.to_string().as_str())
+    }
+
     pub fn set_log_to_file(mut self, to_file: bool) -> Self {
         self.log_to_file = to_file;
         self
     }
 
+    /// Directory of the log files.
+    ///
+    /// Without one, `logs` in the working directory is used when [`Logger::start`] runs, falling back to
+    /// `$XDG_STATE_HOME/<app_name>/logs` and then to the temp dir if there is no working directory.
     pub fn set_log_path(mut self, path: &str) -> Self {
-        self.log_path = PathBuf::from(path);
+        self.log_path = Some(PathBuf::from(path));
         self
     }
 
@@ -692,125 +190,364 @@ impl Logger {
         self
     }
 
+    pub fn set_rotation(mut self, rotation: RotationPolicy) -> Self {
+        self.rotation = rotation;
+        self
+    }
+
+    pub fn set_file_naming(mut self, file_naming: FileNaming) -> Self {
+        self.file_naming = file_naming;
+        self
+    }
+
+    /// Gzips rotated files in the background; `log_file_count` then counts the compressed files.
+    pub fn set_compress_rotated_files(mut self, compress: bool) -> Self {
+        self.compress_rotated_files = compress;
+        self
+    }
+
+    /// Removes rotated files last modified more than `max_age` ago.
+    pub fn set_log_max_age(mut self, max_age: Duration) -> Self {
+        self.log_max_age = Some(max_age);
+        self
+    }
+
+    /// Removes the oldest rotated files while the files of the main log and of the module logs without
+    /// their own limits together exceed `max_total_size` bytes.
+    pub fn set_log_max_total_size(mut self, max_total_size: u64) -> Self {
+        self.log_max_total_size = Some(max_total_size);
+        self
+    }
+
     pub fn set_output_to_console(mut self, output_console: bool) -> Self {
         self.output_console = output_console;
         self
     }
 
-    pub fn add_module_log(mut self, module_key: &str, log_name: &str) -> Self {
-        self.module_logs.push((module_key.to_string(), log_name.to_string()));
+    pub fn set_format(mut self, format: LogFormat) -> Self {
+        self.format = format;
         self
     }
 
-    pub fn add_filter(mut self, filter: &str) -> Self {
-        self.filter.push(filter.to_string());
+    /// Hands records to a background thread through a queue of `queue_size` records instead of
+    /// writing them on the logging thread; `overflow` decides what happens while the queue is full.
+    ///
+    /// Queued records are written when the handle is flushed, shut down or dropped. [`Logger::start`]
+    /// rejects a `queue_size` of 0, like `async_queue_size` in a config file.
+    pub fn set_async(mut self, queue_size: usize, overflow: OverflowPolicy) -> Self {
+        self.async_queue_size = Some(queue_size);
+        self.overflow_policy = overflow;
         self
     }
 
-    fn new_log(&self, log_name: &str, filters: Vec<String>) -> Result<Box<dyn log::Log>, FlexiLoggerError> {
-        let mut logger = flexi_logger::Logger::try_with_env_or_str(self.log_level.as_str())?;
-        if self.log_to_file {
-            let mut base_name = self.app_name.clone();
-            if !self.instance_id.is_empty() {
-                base_name = format!("{}_{}", self.app_name, self.instance_id);
-            }
-            if !log_name.is_empty() {
-                base_name = format!("{}_{}", base_name, log_name);
-            }
-            logger = logger.log_to_file(FileSpec::default().directory(self.log_path.as_path()).basename(base_name.as_str()))
-                .rotate(Criterion::Size(self.log_file_size), // 文件大小达到 10MB 时轮转
-                        Naming::Numbers, // 使用数字命名轮转文件
-                        Cleanup::KeepLogFiles(self.log_file_count), // 保留最近 7 个日志文件
-                );
-        }
-        if !self.output_console {
-            logger = logger.duplicate_to_stderr(Duplicate::None);
-        } else {
-            logger = logger.duplicate_to_stderr(Duplicate::All);
-        }
+    /// Keeps the last records written to any log in memory, formatted like the main log, for
+    /// [`SfoLogHandle::recent`].
+    pub fn set_recent_buffer(mut self, limit: RecentLimit) -> Self {
+        self.recent_limit = Some(limit);
+        self
+    }
 
-        logger = logger.filter(Box::new(SfoLogFilter::new(filters)));
+    /// Writes records whose target starts with `module_key` to their own log named `log_name`, and to the main log as well.
+    ///
+    /// Keys match whole `::` segments, `my_app::net` doesn't match `my_app::network`, and a `*` segment matches
+    /// any one segment, e.g. `my_app::*::rpc`. A record goes to the route whose key matches the most segments.
+    pub fn add_module_log(self, module_key: &str, log_name: &str) -> Self {
+        self.add_module_log_with_mode(module_key, log_name, RouteMode::Duplicate)
+    }
 
-        let (log, _) = logger.format(custom_format).build()?;
-        Ok(log)
+    /// Like [`Logger::add_module_log`], with `mode` deciding which of the records also reach the main log.
+    pub fn add_module_log_with_mode(self, module_key: &str, log_name: &str, mode: RouteMode) -> Self {
+        self.add_module_log_with_options(module_key, log_name, ModuleLogOptions::new().set_mode(mode))
     }
 
-    #[cfg(not(feature = "nolog"))]
-    pub fn start(self) -> Result<(), FlexiLoggerError> {
-        let main_log = self.new_log("", self.filter.clone())?;
-        let mut module_logs = Vec::new();
-        for (match_key, log_name) in self.module_logs.iter() {
-            module_logs.push((match_key.clone(), self.new_log(log_name.as_str(), vec![])?));
-        }
-        
-        let sfo_log = SfoLogger {
-            main_logger: main_log,
-            module_loggers: module_logs,
-        };
-        
-        log::set_boxed_logger(Box::new(sfo_log))?;
-        
-        Ok(())
+    /// Like [`Logger::add_module_log`], with level, rotation, retention, format and console settings of its own.
+    ///
+    /// ```no_run
+    /// use sfo_log::{Logger, ModuleLogOptions};
+    ///
+    /// let _handle = Logger::new("my_app")
+    ///     .set_log_to_file(true)
+    ///     .add_module_log_with_options("my_app::p2p", "p2p", ModuleLogOptions::new()
+    ///         .set_log_level("trace")
+    ///         .set_log_file_size(1024 * 1024)
+    ///         .set_log_file_count(3))
+    ///     .start()
+    ///     .unwrap();
+    /// ```
+    pub fn add_module_log_with_options(mut self, module_key: &str, log_name: &str, options: ModuleLogOptions) -> Self {
+        self.module_logs.push(ModuleRoute {
+            module: module_key.to_string(),
+            name: log_name.to_string(),
+            options,
+        });
+        self
     }
 
-    #[cfg(feature = "nolog")]
-    pub fn start(self) -> Result<(), FlexiLoggerError> {
-        Ok(())
+    /// Writes records with a level in `levels` from every target to their own log named `log_name`, in addition
+    /// to the logs they go to anyway, e.g. `..=Level::WARN` for an `app_error` log of warnings and errors.
+    pub fn add_level_log(self, log_name: &str, levels: impl RangeBounds<Level>) -> Self {
+        self.add_predicate_log(log_name, LogPredicate::new().levels(levels), ModuleLogOptions::new())
     }
-}
 
-struct SfoLogFilter {
-    filters: HashSet<String>,
-}
+    /// Writes records matching `predicate` to their own log named `log_name`, in addition to the logs they go to anyway.
+    ///
+    /// The mode of `options` is ignored, its other settings apply like for a module log, except that
+    /// records aren't repeated on the console unless `options` asks for it. Nothing is written without
+    /// [`Logger::set_log_to_file`].
+    ///
+    /// ```no_run
+    /// use sfo_log::{Level, LogPredicate, Logger, ModuleLogOptions};
+    ///
+    /// let _handle = Logger::new("my_app")
+    ///     .set_log_to_file(true)
+    ///     .add_level_log("error", ..=Level::WARN)
+    ///     .add_predicate_log("net_debug", LogPredicate::new().target("my_app::net").levels(Level::DEBUG..),
+    ///         ModuleLogOptions::new().set_log_level("trace"))
+    ///     .start()
+    ///     .unwrap();
+    /// ```
+    pub fn add_predicate_log(mut self, log_name: &str, predicate: LogPredicate, options: ModuleLogOptions) -> Self {
+        self.predicate_logs.push(PredicateRoute::new(log_name, predicate, options));
+        self
+    }
 
-impl SfoLogFilter {
-    fn new(filters: Vec<String>) -> Self {
-        Self {
-            filters: filters.into_iter().map(|filter| filter.to_string()).collect(),
+    /// Drops the records whose target starts with the `::` segments of `filter`, e.g. `hyper`.
+    pub fn add_filter(self, filter: &str) -> Self {
+        self.add_filter_rule(FilterRule::deny().target(filter))
+    }
+
+    /// Appends `rule` to the filter rules of the main log, the first matching rule decides about a record.
+    ///
+    /// Module and predicate logs without rules of their own use these rules as well.
+    pub fn add_filter_rule(mut self, rule: FilterRule) -> Self {
+        self.filter.push(rule);
+        self
+    }
+
+    /// Polls `path` while the logger is running and applies its changes on top of the settings
+    /// the logger was started with.
+    ///
+    /// Settings missing from the file keep their start values. A file that fails to load is
+    /// reported and ignored, the running configuration stays in place. Levels set through
+    /// [`SfoLogHandle::set_log_level`] and [`SfoLogHandle::set_module_log_level`] stay in place
+    /// across reloads, over the levels of the file.
+    #[cfg(feature = "config")]
+    pub fn watch_config_file(mut self, path: impl AsRef<Path>) -> Self {
+        self.config_watch = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    #[cfg(sfo_log_backend)]
+    fn file_base_name(&self, log_name: &str) -> String {
+        let mut base_name = self.app_name.clone();
+        if !self.instance_id.is_empty() {
+            base_name = format!("{}_{}", self.app_name, self.instance_id);
+        }
+        if !log_name.is_empty() {
+            base_name = format!("{}_{}", base_name, log_name);
+        }
+        base_name
+    }
+
+    #[cfg(sfo_log_backend)]
+    fn resolve_log_path(&self) -> PathBuf {
+        if let Some(log_path) = self.log_path.as_ref() {
+            return log_path.clone();
+        }
+        if let Ok(current_dir) = std::env::current_dir() {
+            return current_dir.join("logs");
+        }
+        // The XDG spec asks to ignore relative paths.
+        if let Some(state_home) = std::env::var_os("XDG_STATE_HOME").map(PathBuf::from).filter(|v| v.is_absolute()) {
+            return state_home.join(self.app_name.as_str()).join("logs");
+        }
+        std::env::temp_dir().join(self.app_name.as_str()).join("logs")
+    }
+
+    // `RUST_LOG` takes precedence over the configured spec.
+    #[cfg(sfo_log_backend)]
+    fn main_levels(&self) -> Result<LevelDirectives, SfoLogError> {
+        match std::env::var("RUST_LOG").ok().filter(|v| !v.trim().is_empty()) {
+            Some(spec) => parse_directives(spec.as_str()),
+            None => parse_directives(self.log_level.as_str()),
         }
     }
-}
 
-impl LogLineFilter for SfoLogFilter {
-    fn write(&self, now: &mut DeferredNow, record: &Record, log_line_writer: &dyn LogLineWriter) -> std::io::Result<()> {
-        let module = if let Some((first, _)) = record.metadata().target().split_once("::") {
-            first
+    // Settings missing from `options` fall back to the ones of the main log.
+    #[cfg(sfo_log_backend)]
+    fn new_log(&self, log_path: &Path, log_name: &str, options: &ModuleLogOptions, formatter: Arc<LineFormatter>, append: bool) -> Result<(Box<dyn log::Log>, LoggerHandle), SfoLogError> {
+        // Levels are checked against the `LevelDirectives` before records reach flexi_logger.
+        let mut logger = flexi_logger::Logger::with(LogSpecification::trace());
+        if self.log_to_file {
+            let base_name = self.file_base_name(log_name);
+            let rotation = options.rotation.unwrap_or(self.rotation);
+            let log_file_count = options.log_file_count.unwrap_or(self.log_file_count);
+            logger = logger.log_to_file(FileSpec::default().directory(log_path).basename(base_name.as_str()))
+                .rotate(rotation.criterion(options.log_file_size.unwrap_or(self.log_file_size)),
+                        self.file_naming.naming(rotation),
+                        if self.compress_rotated_files {
+                            Cleanup::KeepCompressedFiles(log_file_count)
+                        } else {
+                            Cleanup::KeepLogFiles(log_file_count) // 保留最近 7 个日志文件
+                        },
+                );
+            if append {
+                logger = logger.append();
+            }
+        }
+        if !options.output_console.unwrap_or(self.output_console) {
+            logger = logger.duplicate_to_stderr(Duplicate::None);
         } else {
-            record.metadata().target()
-        };
-        
-        if self.filters.contains(module) {
-            return Ok(());
+            logger = logger.duplicate_to_stderr(Duplicate::All);
         }
 
-        log_line_writer.write(now, record)
+        logger = logger.filter(Box::new(SfoLogFilter::new(formatter)));
+
+        logger.format(preformatted).build().map_err(|e| SfoLogError::Io {
+            log: log_name.to_string(),
+            path: log_path.to_path_buf(),
+            source: match e {
+                FlexiLoggerError::OutputIo(e) | FlexiLoggerError::OutputCleanupThread(e) => e,
+                FlexiLoggerError::OutputBadDirectory => std::io::Error::from(std::io::ErrorKind::NotADirectory),
+                FlexiLoggerError::OutputBadFile => std::io::Error::from(std::io::ErrorKind::IsADirectory),
+                e => std::io::Error::other(e),
+            },
+        })
     }
-}
 
-struct SfoLogger {
-    main_logger: Box<dyn log::Log>,
-    module_loggers: Vec<(String, Box<dyn log::Log>)>,
-}
+    // A module or predicate log with its level directives; format, level and filter rules fall back to the main log.
+    #[cfg(sfo_log_backend)]
+    fn new_route_log(&self, log_path: &Path, log_name: &str, options: &ModuleLogOptions, main_formatter: &Arc<LineFormatter>, main_levels: &LevelDirectives, append: bool) -> Result<(LevelDirectives, CompiledFilter, Box<dyn log::Log>, LoggerHandle), SfoLogError> {
+        let formatter = match options.format.as_ref() {
+            Some(format) => Arc::new(LineFormatter::new(format, self.app_name.as_str(), self.instance_id.as_str())?),
+            None => main_formatter.clone(),
+        };
+        let levels = match options.log_level.as_ref() {
+            Some(level) => parse_directives(level.as_str())?,
+            None => main_levels.clone(),
+        };
+        let filter = CompiledFilter::new(if options.filters.is_empty() { &self.filter } else { &options.filters })?;
+        let (logger, handle) = self.new_log(log_path, log_name, options, formatter, append)?;
+        Ok((levels, filter, logger, handle))
+    }
 
-impl log::Log for SfoLogger {
-    fn enabled(&self, metadata: &Metadata) -> bool {
-        self.main_logger.enabled(metadata)
+    // `log_path` is resolved once by `start`, so a reload keeps the directory even if the working directory is gone.
+    // `append` keeps writing to the current files instead of rotating them, used when rebuilding on reload.
+    #[cfg(sfo_log_backend)]
+    pub(crate) fn build_state(&self, log_path: &Path, append: bool) -> Result<SfoLogState, SfoLogError> {
+        let formatter = Arc::new(LineFormatter::new(&self.format, self.app_name.as_str(), self.instance_id.as_str())?);
+        let main_levels = self.main_levels()?;
+        let main_filter = CompiledFilter::new(&self.filter)?;
+        let (main_logger, main_handle) = self.new_log(log_path, "", &ModuleLogOptions::default(), formatter.clone(), append)?;
+        let mut module_loggers = Vec::new();
+        for route in self.module_logs.iter() {
+            let (levels, filter, logger, handle) = self.new_route_log(log_path, route.name.as_str(), &route.options, &formatter, &main_levels, append)?;
+            module_loggers.push(ModuleLogger {
+                module: route.module.clone(),
+                mode: route.options.mode,
+                levels: RwLock::new(levels),
+                filter,
+                logger,
+                handle,
+            });
+        }
+        let mut predicate_loggers = Vec::new();
+        // Predicate logs are files of their own, without files there is nothing to build.
+        let predicate_logs = if self.log_to_file { self.predicate_logs.as_slice() } else { &[] };
+        for route in predicate_logs.iter() {
+            let (levels, filter, logger, handle) = self.new_route_log(log_path, route.name.as_str(), &route.options, &formatter, &main_levels, append)?;
+            predicate_loggers.push(PredicateLogger {
+                predicate: CompiledPredicate::new(route.predicate.target.as_deref(), &route.predicate.levels),
+                levels: RwLock::new(levels),
+                filter,
+                logger,
+                handle,
+            });
+        }
+
+        Ok(SfoLogState {
+            main_logger,
+            main_handle,
+            main_levels: RwLock::new(main_levels),
+            main_filter,
+            routes: RouteTable::new(module_loggers.iter().map(|v| v.module.as_str())),
+            module_loggers,
+            predicate_loggers,
+            recent: self.recent_limit.map(|v| Arc::new(RecentBuffer::new(v))),
+            formatter,
+            retention: self.retention(log_path.to_path_buf()),
+            level_overrides: Default::default(),
+        })
     }
 
-    fn log(&self, record: &Record) {
-        for (module, log) in self.module_loggers.iter() {
-            if record.metadata().target().starts_with(module) {
-                log.log(record);
-                break;
+    #[cfg(sfo_log_backend)]
+    fn retention(&self, log_path: PathBuf) -> Retention {
+        let mut groups = vec![RetentionGroup {
+            max_age: self.log_max_age,
+            max_total_size: self.log_max_total_size,
+            file_bases: vec![self.file_base_name("")],
+        }];
+        let routes = self.module_logs.iter().map(|v| (v.name.as_str(), &v.options))
+            .chain(self.predicate_logs.iter().map(|v| (v.name.as_str(), &v.options)));
+        for (name, options) in routes {
+            let file_base = self.file_base_name(name);
+            if options.has_retention() {
+                groups.push(RetentionGroup {
+                    max_age: options.log_max_age.or(self.log_max_age),
+                    max_total_size: options.log_max_total_size.or(self.log_max_total_size),
+                    file_bases: vec![file_base],
+                });
+            } else {
+                groups[0].file_bases.push(file_base);
             }
         }
-        self.main_logger.log(record);
+        if !self.log_to_file {
+            groups.clear();
+        }
+        Retention {
+            log_path,
+            file_naming: self.file_naming,
+            groups,
+        }
     }
 
-    fn flush(&self) {
-        self.main_logger.flush();
-        for (_, module_logger) in self.module_loggers.iter() {
-            module_logger.flush();
+    #[cfg(sfo_log_backend)]
+    #[cfg_attr(not(feature = "config"), allow(unused_mut))]
+    pub fn start(mut self) -> Result<SfoLogHandle, SfoLogError> {
+        if self.async_queue_size == Some(0) {
+            return Err(LoggerConfigError::InvalidValue {
+                field: "async_queue_size",
+                message: "must be greater than 0".to_string(),
+            }.into());
         }
+        let log_path = self.resolve_log_path();
+        let state = Arc::new(RwLock::new(self.build_state(log_path.as_path(), false)?));
+
+        let async_writer = match self.async_queue_size {
+            Some(queue_size) => Some(AsyncWriter::spawn(queue_size, self.overflow_policy, state.clone())?),
+            None => None,
+        };
+        install_logger(SfoLogger::new(state.clone(), async_writer.as_ref().map(|v| v.queue().clone())))?;
+        read_state(&state).sync_max_level();
+
+        #[cfg(feature = "config")]
+        let config_watch = self.config_watch.take();
+        #[cfg(not(feature = "config"))]
+        let config_watch: Option<PathBuf> = None;
+        let mut background_threads = Vec::new();
+        // A reloaded config may set retention limits, so the sweeper also runs while watching.
+        if read_state(&state).retention.is_limited() || config_watch.is_some() {
+            background_threads.push(sweep_retention(state.clone())?);
+        }
+        #[cfg(feature = "config")]
+        if let Some(path) = config_watch {
+            background_threads.push(watch_config(self, path, log_path, state.clone())?);
+        }
+        Ok(SfoLogHandle::new(state, background_threads, async_writer))
+    }
+
+    #[cfg(not(sfo_log_backend))]
+    pub fn start(self) -> Result<SfoLogHandle, SfoLogError> {
+        Ok(SfoLogHandle::new())
     }
 }
@@ -0,0 +1,252 @@
+use std::cell::RefCell;
+use std::io::Write;
+use std::path::Path;
+use std::thread;
+use chrono::{DateTime, Local, SecondsFormat};
+use flexi_logger::{DeferredNow, Record};
+use crate::fields::collect_fields;
+use crate::format::{Align, CompiledTemplate, Field, LogFormat, Padding, Segment, TemplateError, TEXT_TEMPLATE};
+
+/// Formats records of one logger; it holds the logger settings a flexi_logger format function can't see.
+pub(crate) struct LineFormatter {
+    template: Option<CompiledTemplate>,
+    app_name: String,
+    instance_id: String,
+}
+
+impl LineFormatter {
+    pub(crate) fn new(format: &LogFormat, app_name: &str, instance_id: &str) -> Result<Self, TemplateError> {
+        let template = match format {
+            LogFormat::Text => Some(CompiledTemplate::compile(TEXT_TEMPLATE)?),
+            LogFormat::Json => None,
+            LogFormat::Template(template) => Some(CompiledTemplate::compile(template.as_str())?),
+        };
+        Ok(Self {
+            template,
+            app_name: app_name.to_string(),
+            instance_id: instance_id.to_string(),
+        })
+    }
+
+    pub(crate) fn write(&self, writer: &mut dyn Write, now: &mut DeferredNow, record: &Record) -> std::io::Result<()> {
+        QUEUED_ORIGIN.with_borrow(|origin| match origin {
+            Some(origin) => self.write_origin(writer, origin, record),
+            None => self.write_origin(writer, &RecordOrigin::current(*now.now()), record),
+        })
+    }
+
+    fn write_origin(&self, writer: &mut dyn Write, origin: &RecordOrigin, record: &Record) -> std::io::Result<()> {
+        match self.template.as_ref() {
+            Some(template) => self.template_format(template, writer, origin, record),
+            None => self.json_format(writer, origin, record),
+        }
+    }
+
+    fn template_format(&self, template: &CompiledTemplate, writer: &mut dyn Write, origin: &RecordOrigin, record: &Record) -> std::io::Result<()> {
+        for segment in template.segments.iter() {
+            match segment {
+                Segment::Literal(literal) => writer.write_all(literal.as_bytes())?,
+                Segment::Timestamp(time_format) => write!(writer, "{}", origin.time.format(time_format.as_str()))?,
+                Segment::Field(Field::Message, None) => {
+                    write!(writer, "{}", record.args())?;
+                    write_fields_text(writer, record)?;
+                }
+                Segment::Field(field, padding) => {
+                    let value = match field {
+                        Field::Level => record.level().to_string(),
+                        Field::Target => record.target().to_string(),
+                        Field::Module => record_module(record).to_string(),
+                        Field::File => record_file_name(record),
+                        Field::Path => record.file().unwrap_or("<unknown>").to_string(),
+                        Field::Line => record.line().unwrap_or(0).to_string(),
+                        Field::Thread => origin.thread.clone(),
+                        Field::ThreadId => origin.thread_id.clone(),
+                        Field::Pid => std::process::id().to_string(),
+                        Field::AppName => self.app_name.clone(),
+                        Field::InstanceId => self.instance_id.clone(),
+                        Field::Message => {
+                            let mut message = Vec::new();
+                            write!(message, "{}", record.args())?;
+                            write_fields_text(&mut message, record)?;
+                            String::from_utf8_lossy(message.as_slice()).to_string()
+                        }
+                    };
+                    match padding {
+                        None => writer.write_all(value.as_bytes())?,
+                        Some(Padding { align: Align::Left, width }) => write!(writer, "{:<width$}", value, width = width)?,
+                        Some(Padding { align: Align::Right, width }) => write!(writer, "{:>width$}", value, width = width)?,
+                        Some(Padding { align: Align::Center, width }) => write!(writer, "{:^width$}", value, width = width)?,
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn json_format(&self, writer: &mut dyn Write, origin: &RecordOrigin, record: &Record) -> std::io::Result<()> {
+        write!(writer, "{{\"timestamp\":")?;
+        write_json_str(writer, origin.time.to_rfc3339_opts(SecondsFormat::Millis, false).as_str())?;
+        write!(writer, ",\"level\":\"{}\",\"target\":", record.level())?;
+        write_json_str(writer, record.target())?;
+        write!(writer, ",\"module\":")?;
+        write_json_str(writer, record_module(record))?;
+        write!(writer, ",\"file\":")?;
+        write_json_str(writer, record.file().unwrap_or("<unknown>"))?;
+        match record.line() {
+            Some(line) => write!(writer, ",\"line\":{}", line)?,
+            None => write!(writer, ",\"line\":null")?,
+        }
+        write!(writer, ",\"thread\":")?;
+        write_json_str(writer, origin.thread.as_str())?;
+        write!(writer, ",\"app_name\":")?;
+        write_json_str(writer, self.app_name.as_str())?;
+        write!(writer, ",\"instance_id\":")?;
+        write_json_str(writer, self.instance_id.as_str())?;
+        write!(writer, ",\"message\":")?;
+        write_json_str(writer, record.args().to_string().as_str())?;
+        if record.key_values().count() > 0 {
+            write!(writer, ",\"fields\":{{")?;
+            for (i, (key, value)) in collect_fields(record.key_values()).iter().enumerate() {
+                if i > 0 {
+                    write!(writer, ",")?;
+                }
+                write_json_str(writer, key.as_str())?;
+                write!(writer, ":")?;
+                serde_json::to_writer(&mut *writer, value).map_err(std::io::Error::other)?;
+            }
+            write!(writer, "}}")?;
+        }
+        write!(writer, "}}")
+    }
+}
+
+// Records reaching flexi_logger already carry the formatted line as their message.
+pub(crate) fn preformatted(writer: &mut dyn Write, _now: &mut DeferredNow, record: &Record) -> std::io::Result<()> {
+    write!(writer, "{}", record.args())
+}
+
+fn record_file_name(record: &Record) -> String {
+    match record.file() {
+        None => {
+            "<unknown>".to_string()
+        }
+        Some(path) => {
+            Path::new(path).file_name().map(|v| v.to_string_lossy().to_string()).unwrap_or("<unknown>".to_string())
+        }
+    }
+}
+
+fn record_module<'a>(record: &Record<'a>) -> &'a str {
+    if let Some((first, _)) = record.metadata().target().split_once("::") {
+        first
+    } else {
+        record.metadata().target()
+    }
+}
+
+/// Thread and time of the call that produced a record.
+pub(crate) struct RecordOrigin {
+    thread: String,
+    thread_id: String,
+    time: DateTime<Local>,
+}
+
+impl RecordOrigin {
+    pub(crate) fn current(time: DateTime<Local>) -> Self {
+        let thread = thread::current();
+        Self {
+            thread: match thread.name() {
+                Some(name) => name.to_string(),
+                None => format!("{:?}", thread.id()),
+            },
+            thread_id: format!("{:?}", thread.id()),
+            time,
+        }
+    }
+}
+
+thread_local! {
+    // Set by the async writer while a queued record is formatted on its own thread.
+    static QUEUED_ORIGIN: RefCell<Option<RecordOrigin>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn with_origin<R>(origin: RecordOrigin, f: impl FnOnce() -> R) -> R {
+    QUEUED_ORIGIN.set(Some(origin));
+    let result = f();
+    QUEUED_ORIGIN.set(None);
+    result
+}
+
+fn write_fields_text(writer: &mut dyn Write, record: &Record) -> std::io::Result<()> {
+    if record.key_values().count() == 0 {
+        return Ok(());
+    }
+    for (key, value) in collect_fields(record.key_values()) {
+        write!(writer, " {}={}", key, value)?;
+    }
+    Ok(())
+}
+
+// serde_json escapes quotes, backslashes, newlines and every other control character.
+fn write_json_str(writer: &mut dyn Write, value: &str) -> std::io::Result<()> {
+    serde_json::to_writer(writer, value).map_err(std::io::Error::other)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+    use tracing::log;
+    use crate::fields::{FieldValue, Fields};
+    use super::*;
+
+    fn write_line(format: LogFormat, target: &str, message: &str, fields: &Fields) -> String {
+        let formatter = LineFormatter::new(&format, "app", "1").unwrap();
+        let mut line = Vec::new();
+        formatter.write(&mut line, &mut DeferredNow::new(), &log::Record::builder()
+            .level(log::Level::Warn)
+            .target(target)
+            .file(Some("src/net.rs"))
+            .line(Some(42))
+            .args(format_args!("{}", message))
+            .key_values(fields)
+            .build()).unwrap();
+        String::from_utf8(line).unwrap()
+    }
+
+    #[test]
+    fn json_escapes_strings() {
+        let message = "say \"hi\"\nthen\ttab\u{1}";
+        let target = "net\"\n\t\u{1}";
+        let line = write_line(LogFormat::Json, target, message, &vec![]);
+        assert!(!line.contains('\n'));
+        let value: serde_json::Value = serde_json::from_str(line.as_str()).unwrap();
+        let keys: BTreeSet<&str> = value.as_object().unwrap().keys().map(|v| v.as_str()).collect();
+        assert_eq!(keys, BTreeSet::from(["timestamp", "level", "target", "module", "file", "line", "thread", "app_name", "instance_id", "message"]));
+        assert_eq!(value["message"], message);
+        assert_eq!(value["target"], target);
+        assert_eq!(value["level"], "WARN");
+        assert_eq!(value["file"], "src/net.rs");
+        assert_eq!(value["line"], 42);
+        assert_eq!(value["app_name"], "app");
+        assert_eq!(value["instance_id"], "1");
+    }
+
+    #[test]
+    fn writes_fields() {
+        let fields: Fields = vec![
+            ("bytes".to_string(), FieldValue::U64(512)),
+            ("ok".to_string(), FieldValue::Bool(true)),
+            ("ratio".to_string(), FieldValue::F64(0.5)),
+            ("peer".to_string(), FieldValue::Str("a \"b\"".to_string())),
+        ];
+        let line = write_line(LogFormat::Json, "net", "sent", &fields);
+        let value: serde_json::Value = serde_json::from_str(line.as_str()).unwrap();
+        assert_eq!(value["message"], "sent");
+        assert_eq!(value["fields"], serde_json::json!({"bytes": 512, "ok": true, "ratio": 0.5, "peer": "a \"b\""}));
+
+        let line = write_line(LogFormat::Template("{level} {msg}".to_string()), "net", "sent", &fields);
+        assert_eq!(line, "WARN sent bytes=512 ok=true ratio=0.5 peer=a \"b\"");
+        let line = write_line(LogFormat::Template("[{msg:<30}]".to_string()), "net", "sent", &fields[..1].to_vec());
+        assert_eq!(line, format!("[{:<30}]", "sent bytes=512"));
+    }
+}
@@ -0,0 +1,308 @@
+//! Stand-ins for the `tracing` macros, span API and [`SfoLogLayer`] without a logging backend; they record nothing.
+
+use std::marker::PhantomData;
+
+/// Stand-in for `tracing::Level`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Level(u8);
+
+impl Level {
+    pub const ERROR: Level = Level(1);
+    pub const WARN: Level = Level(2);
+    pub const INFO: Level = Level(3);
+    pub const DEBUG: Level = Level(4);
+    pub const TRACE: Level = Level(5);
+}
+
+/// Stand-in for `tracing::Span`, every span is disabled.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Span {}
+
+impl Span {
+    pub fn none() -> Span {
+        Span {}
+    }
+
+    pub fn current() -> Span {
+        Span {}
+    }
+
+    pub fn enter(&self) -> Entered<'_> {
+        Entered(PhantomData)
+    }
+
+    pub fn entered(self) -> EnteredSpan {
+        EnteredSpan(self)
+    }
+
+    pub fn in_scope<F: FnOnce() -> T, T>(&self, f: F) -> T {
+        f()
+    }
+
+    pub fn record<Q: ?Sized, V>(&self, _field: &Q, _value: V) -> &Self {
+        self
+    }
+
+    pub fn follows_from<T>(&self, _from: T) -> &Self {
+        self
+    }
+
+    pub fn is_none(&self) -> bool {
+        true
+    }
+
+    pub fn is_disabled(&self) -> bool {
+        true
+    }
+}
+
+/// Guard returned by [`Span::enter`].
+#[derive(Debug)]
+pub struct Entered<'a>(PhantomData<&'a Span>);
+
+/// Guard returned by [`Span::entered`].
+#[derive(Debug)]
+pub struct EnteredSpan(Span);
+
+impl EnteredSpan {
+    pub fn exit(self) -> Span {
+        self.0
+    }
+}
+
+// Accepts what `parent:` takes in tracing; one impl per shape keeps `parent: None` inferable.
+#[doc(hidden)]
+pub trait __Parent {}
+
+impl __Parent for Span {}
+impl __Parent for &Span {}
+impl __Parent for Option<&Span> {}
+
+#[doc(hidden)]
+pub fn __parent(_parent: impl __Parent) {}
+
+/// Stand-in for `tracing::Instrument`, values are returned unchanged.
+pub trait Instrument: Sized {
+    fn instrument(self, _span: Span) -> Self {
+        self
+    }
+
+    fn in_current_span(self) -> Self {
+        self
+    }
+}
+
+impl<T: Sized> Instrument for T {}
+
+/// Stand-in for the `tracing_subscriber` layer, there is nothing to install.
+#[derive(Debug, Clone, Default)]
+pub struct SfoLogLayer {}
+
+impl SfoLogLayer {
+    pub fn new() -> Self {
+        Self {}
+    }
+
+    pub fn install() -> Result<(), std::convert::Infallible> {
+        Ok(())
+    }
+}
+
+// Expands the arguments of the logging macros to statements that type-check every field value and
+// format argument. The level macros run it inside a closure that is never called, so nothing is
+// evaluated at runtime.
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __sfo_log_nolog {
+    // `name:`, `target:` and `parent:` prefixes.
+    (@$mode:ident name: $name:expr, $($rest:tt)*) => {
+        let _ = &$name;
+        $crate::__sfo_log_nolog!(@$mode $($rest)*);
+    };
+    (@$mode:ident target: $target:expr, $($rest:tt)*) => {
+        let _: &str = $target;
+        $crate::__sfo_log_nolog!(@$mode $($rest)*);
+    };
+    (@$mode:ident parent: $parent:expr, $($rest:tt)*) => {
+        $crate::__parent($parent);
+        $crate::__sfo_log_nolog!(@$mode $($rest)*);
+    };
+
+    // What follows the prefixes for each macro.
+    (@event $level:expr, $($rest:tt)*) => {
+        let _: $crate::Level = $level;
+        $crate::__sfo_log_nolog!(@fields $($rest)*);
+    };
+    (@span $level:expr, $name:expr $(, $($rest:tt)*)?) => {
+        let _: $crate::Level = $level;
+        let _: &str = $name;
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@level_span $name:expr $(, $($rest:tt)*)?) => {
+        let _: &str = $name;
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@level $($rest:tt)*) => {
+        $crate::__sfo_log_nolog!(@fields $($rest)*);
+    };
+
+    // Fields, then the message.
+    (@fields) => {};
+    (@fields { $($fields:tt)* } $(, $($rest:tt)*)?) => {
+        $crate::__sfo_log_nolog!(@fields $($fields)*);
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@fields $($k:ident).+ = ?$value:expr $(, $($rest:tt)*)?) => {
+        let _ = format_args!("{:?}", $value);
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@fields $($k:ident).+ = %$value:expr $(, $($rest:tt)*)?) => {
+        let _ = format_args!("{}", $value);
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@fields $($k:ident).+ = $value:expr $(, $($rest:tt)*)?) => {
+        let _ = &$value;
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@fields $k:literal = ?$value:expr $(, $($rest:tt)*)?) => {
+        let _ = format_args!("{:?}", $value);
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@fields $k:literal = %$value:expr $(, $($rest:tt)*)?) => {
+        let _ = format_args!("{}", $value);
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@fields $k:literal = $value:expr $(, $($rest:tt)*)?) => {
+        let _ = &$value;
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@fields ?$($k:ident).+ $(, $($rest:tt)*)?) => {
+        let _ = format_args!("{:?}", $($k).+);
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@fields %$($k:ident).+ $(, $($rest:tt)*)?) => {
+        let _ = format_args!("{}", $($k).+);
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@fields $($k:ident).+ $(, $($rest:tt)*)?) => {
+        let _ = &$($k).+;
+        $crate::__sfo_log_nolog!(@fields $($($rest)*)?);
+    };
+    (@fields $message:literal $($arg:tt)*) => {
+        let _ = format_args!($message $($arg)*);
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@level $($arg)+);
+        };
+    }};
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@level $($arg)+);
+        };
+    }};
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@level $($arg)+);
+        };
+    }};
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@level $($arg)+);
+        };
+    }};
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@level $($arg)+);
+        };
+    }};
+}
+
+#[macro_export]
+macro_rules! event {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@event $($arg)+);
+        };
+    }};
+}
+
+#[macro_export]
+macro_rules! span {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@span $($arg)+);
+        };
+        $crate::Span::none()
+    }};
+}
+
+#[macro_export]
+macro_rules! trace_span {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@level_span $($arg)+);
+        };
+        $crate::Span::none()
+    }};
+}
+
+#[macro_export]
+macro_rules! debug_span {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@level_span $($arg)+);
+        };
+        $crate::Span::none()
+    }};
+}
+
+#[macro_export]
+macro_rules! info_span {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@level_span $($arg)+);
+        };
+        $crate::Span::none()
+    }};
+}
+
+#[macro_export]
+macro_rules! warn_span {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@level_span $($arg)+);
+        };
+        $crate::Span::none()
+    }};
+}
+
+#[macro_export]
+macro_rules! error_span {
+    ($($arg:tt)+) => {{
+        let _ = || {
+            $crate::__sfo_log_nolog!(@level_span $($arg)+);
+        };
+        $crate::Span::none()
+    }};
+}
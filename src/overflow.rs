@@ -0,0 +1,27 @@
+use serde::Deserialize;
+
+/// What the async writer does with a new record while its queue is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+    /// Waits until the writer thread has made room.
+    #[default]
+    Block,
+    /// Discards the new record.
+    DropNewest,
+    /// Discards the oldest queued record to make room for the new one.
+    DropOldest,
+}
+
+impl std::str::FromStr for OverflowPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "block" => Ok(OverflowPolicy::Block),
+            "drop_newest" => Ok(OverflowPolicy::DropNewest),
+            "drop_oldest" => Ok(OverflowPolicy::DropOldest),
+            _ => Err(format!("unknown overflow policy \"{}\", expected block, drop_newest or drop_oldest", s)),
+        }
+    }
+}
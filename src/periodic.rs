@@ -0,0 +1,38 @@
+use std::sync::mpsc::{channel, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use crate::SfoLogError;
+
+/// A named background thread running a task right away and then every `interval`, stopped when dropped.
+pub(crate) struct PeriodicThread {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PeriodicThread {
+    pub(crate) fn spawn(name: &'static str, interval: Duration, mut task: impl FnMut() + Send + 'static) -> Result<Self, SfoLogError> {
+        let (stop, stopped) = channel::<()>();
+        let thread = std::thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || {
+                task();
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                    task();
+                }
+            })
+            .map_err(|e| SfoLogError::Thread { name, source: e })?;
+        Ok(Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        })
+    }
+}
+
+impl Drop for PeriodicThread {
+    fn drop(&mut self) {
+        self.stop.take();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
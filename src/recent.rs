@@ -0,0 +1,176 @@
+#[cfg(sfo_log_backend)]
+use std::collections::VecDeque;
+#[cfg(sfo_log_backend)]
+use std::sync::Mutex;
+#[cfg(sfo_log_backend)]
+use flexi_logger::DeferredNow;
+#[cfg(sfo_log_backend)]
+use tracing::log;
+#[cfg(sfo_log_backend)]
+use crate::line_format::LineFormatter;
+#[cfg(sfo_log_backend)]
+use crate::route_table::KeyPattern;
+use crate::Level;
+
+/// How much the buffer set with [`crate::Logger::set_recent_buffer`] keeps; the oldest records are discarded first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecentLimit {
+    /// Keeps the last `n` records.
+    Records(usize),
+    /// Keeps the last records whose formatted lines add up to at most `n` bytes.
+    Bytes(usize),
+}
+
+/// A record kept in memory, formatted like the main log.
+#[derive(Debug, Clone)]
+pub struct RecentRecord {
+    pub level: Level,
+    pub target: String,
+    pub line: String,
+}
+
+/// The last records written to any log, shared by every state the logger is rebuilt into on reload.
+#[cfg(sfo_log_backend)]
+pub(crate) struct RecentBuffer {
+    limit: RecentLimit,
+    records: Mutex<RecentRecords>,
+}
+
+#[cfg(sfo_log_backend)]
+#[derive(Default)]
+struct RecentRecords {
+    records: VecDeque<RecentRecord>,
+    bytes: usize,
+}
+
+#[cfg(sfo_log_backend)]
+impl RecentBuffer {
+    pub(crate) fn new(limit: RecentLimit) -> Self {
+        Self {
+            limit,
+            records: Mutex::new(RecentRecords::default()),
+        }
+    }
+
+    pub(crate) fn push(&self, formatter: &LineFormatter, record: &log::Record) {
+        let mut line = Vec::new();
+        if formatter.write(&mut line, &mut DeferredNow::new(), record).is_err() {
+            return;
+        }
+        let record = RecentRecord {
+            level: tracing_level(record.level()),
+            target: record.target().to_string(),
+            line: String::from_utf8_lossy(line.as_slice()).into_owned(),
+        };
+        let mut records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        records.bytes += record.line.len();
+        records.records.push_back(record);
+        loop {
+            let full = match self.limit {
+                RecentLimit::Records(max) => records.records.len() > max,
+                RecentLimit::Bytes(max) => records.bytes > max,
+            };
+            if !full {
+                break;
+            }
+            let Some(oldest) = records.records.pop_front() else {
+                break;
+            };
+            records.bytes -= oldest.line.len();
+        }
+    }
+
+    // The newest `limit` matching records, oldest first.
+    pub(crate) fn query(&self, level_min: Level, target_prefix: &str, limit: usize) -> Vec<RecentRecord> {
+        let target_prefix = KeyPattern::new(target_prefix);
+        let records = self.records.lock().unwrap_or_else(|e| e.into_inner());
+        let mut matches: Vec<RecentRecord> = records.records.iter().rev()
+            .filter(|v| v.level <= level_min && target_prefix.matches(v.target.as_str()))
+            .take(limit)
+            .cloned()
+            .collect();
+        matches.reverse();
+        matches
+    }
+}
+
+#[cfg(sfo_log_backend)]
+fn tracing_level(level: log::Level) -> Level {
+    match level {
+        log::Level::Error => Level::ERROR,
+        log::Level::Warn => Level::WARN,
+        log::Level::Info => Level::INFO,
+        log::Level::Debug => Level::DEBUG,
+        log::Level::Trace => Level::TRACE,
+    }
+}
+
+#[cfg(all(test, sfo_log_backend))]
+mod tests {
+    use crate::LogFormat;
+    use super::*;
+
+    fn filled(limit: RecentLimit, records: &[(log::Level, &str, &str)]) -> RecentBuffer {
+        let formatter = LineFormatter::new(&LogFormat::Template("{msg}".to_string()), "app", "").unwrap();
+        let buffer = RecentBuffer::new(limit);
+        for (level, target, message) in records {
+            buffer.push(&formatter, &log::Record::builder()
+                .args(format_args!("{}", message))
+                .level(*level)
+                .target(target)
+                .build());
+        }
+        buffer
+    }
+
+    fn lines(records: Vec<RecentRecord>) -> Vec<String> {
+        records.into_iter().map(|v| v.line).collect()
+    }
+
+    #[test]
+    fn keeps_the_last_records() {
+        let buffer = filled(RecentLimit::Records(2), &[
+            (log::Level::Info, "a", "1"),
+            (log::Level::Info, "a", "2"),
+            (log::Level::Info, "a", "3"),
+        ]);
+        assert_eq!(lines(buffer.query(Level::TRACE, "", 10)), ["2", "3"]);
+    }
+
+    #[test]
+    fn keeps_the_last_bytes() {
+        let buffer = filled(RecentLimit::Bytes(5), &[
+            (log::Level::Info, "a", "12"),
+            (log::Level::Info, "a", "34"),
+            (log::Level::Info, "a", "56"),
+        ]);
+        assert_eq!(lines(buffer.query(Level::TRACE, "", 10)), ["34", "56"]);
+
+        let buffer = filled(RecentLimit::Bytes(5), &[
+            (log::Level::Info, "a", "12"),
+            (log::Level::Info, "a", "123456"),
+        ]);
+        // A line larger than the whole limit isn't kept either.
+        assert!(buffer.query(Level::TRACE, "", 10).is_empty());
+        assert_eq!(buffer.records.lock().unwrap().bytes, 0);
+    }
+
+    #[test]
+    fn queries_by_level_target_and_limit() {
+        let buffer = filled(RecentLimit::Records(16), &[
+            (log::Level::Error, "my_app::net", "1"),
+            (log::Level::Debug, "my_app::net", "2"),
+            (log::Level::Warn, "my_app::network", "3"),
+            (log::Level::Info, "my_app::net::tcp", "4"),
+            (log::Level::Warn, "other", "5"),
+            (log::Level::Warn, "my_app::net", "6"),
+        ]);
+        assert_eq!(lines(buffer.query(Level::INFO, "my_app::net", 10)), ["1", "4", "6"]);
+        assert_eq!(lines(buffer.query(Level::WARN, "", 10)), ["1", "3", "5", "6"]);
+        assert_eq!(lines(buffer.query(Level::TRACE, "my_app", 2)), ["4", "6"]);
+        assert!(buffer.query(Level::TRACE, "my_app::net", 0).is_empty());
+
+        let record = buffer.query(Level::ERROR, "", 1).remove(0);
+        assert_eq!((record.level, record.target.as_str()), (Level::ERROR, "my_app::net"));
+    }
+}
@@ -0,0 +1,199 @@
+use std::path::PathBuf;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing::log;
+use crate::periodic::PeriodicThread;
+use crate::sfo_logger::{read_state, SfoLogState};
+use crate::{FileNaming, SfoLogError};
+
+const RETENTION_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Age and size limits applied on top of flexi_logger's file count cleanup.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Retention {
+    pub(crate) log_path: PathBuf,
+    pub(crate) file_naming: FileNaming,
+    pub(crate) groups: Vec<RetentionGroup>,
+}
+
+/// Routes sharing one set of limits; the total size counts the files of all of them.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RetentionGroup {
+    pub(crate) max_age: Option<Duration>,
+    pub(crate) max_total_size: Option<u64>,
+    // Base names of the files of the routes, e.g. `app` and `app_net`.
+    pub(crate) file_bases: Vec<String>,
+}
+
+impl RetentionGroup {
+    fn is_limited(&self) -> bool {
+        (self.max_age.is_some() || self.max_total_size.is_some()) && !self.file_bases.is_empty()
+    }
+}
+
+impl Retention {
+    pub(crate) fn is_limited(&self) -> bool {
+        self.groups.iter().any(|v| v.is_limited())
+    }
+}
+
+struct LogFile {
+    path: PathBuf,
+    modified: SystemTime,
+    size: u64,
+    removable: bool,
+}
+
+/// Periodically removes rotated files of every route that exceed the retention limits of the running state.
+pub(crate) fn sweep_retention(state: Arc<RwLock<SfoLogState>>) -> Result<PeriodicThread, SfoLogError> {
+    PeriodicThread::spawn("sfo-log-retention", RETENTION_SWEEP_INTERVAL, move || {
+        let retention = read_state(&state).retention.clone();
+        for group in retention.groups.iter().filter(|v| v.is_limited()) {
+            remove_expired(group, collect_log_files(&retention, group));
+        }
+    })
+}
+
+// flexi_logger only lists files once a writer has been used, so the directory is scanned here.
+fn collect_log_files(retention: &Retention, group: &RetentionGroup) -> Vec<LogFile> {
+    let entries = match std::fs::read_dir(retention.log_path.as_path()) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut routes: Vec<Vec<LogFile>> = group.file_bases.iter().map(|_| Vec::new()).collect();
+    for entry in entries.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let Some(route) = group.file_bases.iter().position(|base| {
+            name.strip_prefix(base.as_str())
+                .and_then(|v| v.strip_prefix('_'))
+                .is_some_and(|infix| is_log_file_infix(infix, retention.file_naming))
+        }) else {
+            continue;
+        };
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        routes[route].push(LogFile {
+            removable: !name.ends_with("_rCURRENT.log"),
+            path: entry.path(),
+            modified: metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            size: metadata.len(),
+        });
+    }
+
+    let mut files = Vec::new();
+    for mut route_files in routes {
+        // With timestamp naming the file currently written isn't marked, so the newest file is assumed to be it.
+        if retention.file_naming == FileNaming::Timestamps
+            && let Some(newest) = route_files.iter_mut().max_by_key(|v| v.modified) {
+            newest.removable = false;
+        }
+        files.append(&mut route_files);
+    }
+    files
+}
+
+// Infixes flexi_logger writes with the timestamp formats of `RotationPolicy`, `0` standing for a digit.
+const TIMESTAMP_LAYOUTS: [&str; 3] = ["0000-00-00", "0000-00-00_00", "0000-00-00_00-00-00"];
+
+// `rCURRENT.log`, `r00001.log`, `r00001.log.gz` for numbers, `2026-10-17.log`, `2026-10-17.restart-0001.log.gz` for timestamps.
+fn is_log_file_infix(infix: &str, file_naming: FileNaming) -> bool {
+    let Some(infix) = infix.strip_suffix(".log").or_else(|| infix.strip_suffix(".log.gz")) else {
+        return false;
+    };
+    match file_naming {
+        FileNaming::Numbers => infix == "rCURRENT" || infix.strip_prefix('r').is_some_and(|v| v.len() >= 5 && is_digits(v)),
+        FileNaming::Timestamps => {
+            // flexi_logger appends a restart counter when a file with the same timestamp exists.
+            let timestamp = match infix.split_once(".restart-") {
+                Some((timestamp, restart)) if is_digits(restart) => timestamp,
+                Some(_) => return false,
+                None => infix,
+            };
+            TIMESTAMP_LAYOUTS.iter().any(|layout| matches_layout(timestamp, layout))
+        }
+    }
+}
+
+fn is_digits(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|v| v.is_ascii_digit())
+}
+
+fn matches_layout(value: &str, layout: &str) -> bool {
+    value.len() == layout.len() && value.bytes().zip(layout.bytes()).all(|(v, l)| if l == b'0' { v.is_ascii_digit() } else { v == l })
+}
+
+fn remove_expired(group: &RetentionGroup, mut files: Vec<LogFile>) {
+    files.sort_by_key(|v| v.modified);
+
+    if let Some(max_age) = group.max_age {
+        let now = SystemTime::now();
+        files.retain(|file| {
+            let expired = file.removable && now.duration_since(file.modified).is_ok_and(|age| age > max_age);
+            !(expired && remove_file(file))
+        });
+    }
+
+    if let Some(max_total_size) = group.max_total_size {
+        let mut total_size: u64 = files.iter().map(|v| v.size).sum();
+        for file in files.iter().filter(|v| v.removable) {
+            if total_size <= max_total_size {
+                break;
+            }
+            if remove_file(file) {
+                total_size -= file.size;
+            }
+        }
+    }
+}
+
+fn remove_file(file: &LogFile) -> bool {
+    match std::fs::remove_file(file.path.as_path()) {
+        Ok(()) => true,
+        Err(e) => {
+            log::warn!("remove expired log file {} failed: {}", file.path.display(), e);
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_infixes() {
+        for infix in ["rCURRENT.log", "r00001.log", "r00042.log.gz", "r123456.log"] {
+            assert!(is_log_file_infix(infix, FileNaming::Numbers), "{}", infix);
+        }
+        for infix in ["r1.log", "r2fa_rCURRENT.log", "7_r00001.log", "r00001.txt", "2026-10-17.log", "rCURRENT"] {
+            assert!(!is_log_file_infix(infix, FileNaming::Numbers), "{}", infix);
+        }
+    }
+
+    #[test]
+    fn timestamps_infixes() {
+        for infix in [
+            "2026-10-17.log",
+            "2026-10-17_08.log",
+            "2026-10-17_08-30-05.log.gz",
+            "2026-10-17.restart-0001.log",
+            "2026-10-17_08.restart-0012.log.gz",
+        ] {
+            assert!(is_log_file_infix(infix, FileNaming::Timestamps), "{}", infix);
+        }
+        for infix in [
+            // Files of an instance `7` and of a module log `2fa` next to the main log `app`.
+            "7_2026-10-17.log",
+            "2fa_2026-10-17.log",
+            "2026-10-17_08-30.log",
+            "2026-10-17.restart-.log",
+            "2026-10-17.restart-x.log",
+            "2026-10-1.log",
+            "rCURRENT.log",
+            "2026-10-17.txt",
+        ] {
+            assert!(!is_log_file_infix(infix, FileNaming::Timestamps), "{}", infix);
+        }
+    }
+}
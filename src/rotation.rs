@@ -0,0 +1,90 @@
+#[cfg(sfo_log_backend)]
+use flexi_logger::{Age, Criterion, Naming};
+use serde::Deserialize;
+
+/// When the log file is rotated. Size based policies use [`crate::Logger::set_log_file_size`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RotationPolicy {
+    #[default]
+    Size,
+    Daily,
+    Hourly,
+    /// Rotates when the file reaches the size limit or a new day starts, whichever comes first.
+    SizeOrDaily,
+    /// Rotates when the file reaches the size limit or a new hour starts, whichever comes first.
+    SizeOrHourly,
+}
+
+/// How the current and the rotated log files are named.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileNaming {
+    /// `app_rCURRENT.log`, rotated to `app_r00000.log`, `app_r00001.log`, ...
+    #[default]
+    Numbers,
+    /// Written directly to a file named by the rotation period, e.g. `app_2026-10-17.log` for
+    /// daily rotation or `app_2026-10-17_13.log` for hourly rotation.
+    Timestamps,
+}
+
+#[cfg(sfo_log_backend)]
+impl RotationPolicy {
+    pub(crate) fn criterion(&self, file_size: u64) -> Criterion {
+        match self {
+            RotationPolicy::Size => Criterion::Size(file_size),
+            RotationPolicy::Daily => Criterion::Age(Age::Day),
+            RotationPolicy::Hourly => Criterion::Age(Age::Hour),
+            RotationPolicy::SizeOrDaily => Criterion::AgeOrSize(Age::Day, file_size),
+            RotationPolicy::SizeOrHourly => Criterion::AgeOrSize(Age::Hour, file_size),
+        }
+    }
+
+    fn timestamp_format(&self) -> &'static str {
+        match self {
+            RotationPolicy::Size => "%Y-%m-%d_%H-%M-%S",
+            RotationPolicy::Daily | RotationPolicy::SizeOrDaily => "%Y-%m-%d",
+            RotationPolicy::Hourly | RotationPolicy::SizeOrHourly => "%Y-%m-%d_%H",
+        }
+    }
+}
+
+#[cfg(sfo_log_backend)]
+impl FileNaming {
+    pub(crate) fn naming(&self, rotation: RotationPolicy) -> Naming {
+        match self {
+            FileNaming::Numbers => Naming::Numbers,
+            FileNaming::Timestamps => Naming::TimestampsCustomFormat {
+                current_infix: None,
+                format: rotation.timestamp_format(),
+            },
+        }
+    }
+}
+
+impl std::str::FromStr for RotationPolicy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "size" => Ok(RotationPolicy::Size),
+            "daily" => Ok(RotationPolicy::Daily),
+            "hourly" => Ok(RotationPolicy::Hourly),
+            "size_or_daily" => Ok(RotationPolicy::SizeOrDaily),
+            "size_or_hourly" => Ok(RotationPolicy::SizeOrHourly),
+            _ => Err(format!("unknown rotation \"{}\", expected size, daily, hourly, size_or_daily or size_or_hourly", s)),
+        }
+    }
+}
+
+impl std::str::FromStr for FileNaming {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "numbers" => Ok(FileNaming::Numbers),
+            "timestamps" => Ok(FileNaming::Timestamps),
+            _ => Err(format!("unknown file naming \"{}\", expected numbers or timestamps", s)),
+        }
+    }
+}
@@ -0,0 +1,230 @@
+use std::ops::{Bound, RangeBounds};
+use std::time::Duration;
+use serde::{Deserialize, Deserializer};
+#[cfg(sfo_log_backend)]
+use tracing::log;
+#[cfg(sfo_log_backend)]
+use crate::layer::log_level;
+#[cfg(sfo_log_backend)]
+use crate::route_table::KeyPattern;
+use crate::level::parse_level;
+use crate::{FilterRule, Level, LevelDirectives, LogFormat, RotationPolicy};
+
+/// Whether records of a module log also reach the main log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RouteMode {
+    /// Written to the module log and the main log.
+    #[default]
+    Duplicate,
+    /// Only written to the module log.
+    Exclusive,
+    /// Written to the module log; records at the level or more severe also go to the main log.
+    MainAbove(Level),
+}
+
+impl std::str::FromStr for RouteMode {
+    type Err = String;
+
+    // `duplicate`, `exclusive` or `main_above:<level>`, e.g. `main_above:warn`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mode = s.trim().to_ascii_lowercase();
+        if let Some(level) = mode.strip_prefix("main_above:") {
+            return match parse_level(level)? {
+                Some(level) => Ok(RouteMode::MainAbove(level)),
+                None => Err("main_above needs a level, use exclusive to keep all records out of the main log".to_string()),
+            };
+        }
+        match mode.as_str() {
+            "duplicate" => Ok(RouteMode::Duplicate),
+            "exclusive" => Ok(RouteMode::Exclusive),
+            _ => Err(format!("unknown route mode \"{}\", expected duplicate, exclusive or main_above:<level>", s)),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RouteMode {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// Settings of one module log for [`crate::Logger::add_module_log_with_options`].
+///
+/// Settings left unset are taken from the [`crate::Logger`], the filter rules as well while none are added here.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(not(sfo_log_backend), allow(dead_code))]
+pub struct ModuleLogOptions {
+    pub(crate) mode: RouteMode,
+    pub(crate) log_level: Option<String>,
+    pub(crate) log_file_size: Option<u64>,
+    pub(crate) log_file_count: Option<usize>,
+    pub(crate) rotation: Option<RotationPolicy>,
+    pub(crate) log_max_age: Option<Duration>,
+    pub(crate) log_max_total_size: Option<u64>,
+    pub(crate) format: Option<LogFormat>,
+    pub(crate) output_console: Option<bool>,
+    pub(crate) filters: Vec<FilterRule>,
+}
+
+impl ModuleLogOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set_mode(mut self, mode: RouteMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Level spec of the module log; unlike the main log level it isn't overridden by `RUST_LOG`.
+    pub fn set_log_level(mut self, level: &str) -> Self {
+        self.log_level = Some(level.to_string());
+        self
+    }
+
+    pub fn set_level_directives(self, directives: &LevelDirectives) -> Self {
+        self.set_log_level(directives.to_string().as_str())
+    }
+
+    pub fn set_log_file_size(mut self, size: u64) -> Self {
+        self.log_file_size = Some(size);
+        self
+    }
+
+    pub fn set_log_file_count(mut self, count: usize) -> Self {
+        self.log_file_count = Some(count);
+        self
+    }
+
+    pub fn set_rotation(mut self, rotation: RotationPolicy) -> Self {
+        self.rotation = Some(rotation);
+        self
+    }
+
+    /// Removes rotated files of this log last modified more than `max_age` ago.
+    pub fn set_log_max_age(mut self, max_age: Duration) -> Self {
+        self.log_max_age = Some(max_age);
+        self
+    }
+
+    /// Limits the files of this log on their own instead of counting them towards the limit of the main log.
+    pub fn set_log_max_total_size(mut self, max_total_size: u64) -> Self {
+        self.log_max_total_size = Some(max_total_size);
+        self
+    }
+
+    pub fn set_format(mut self, format: LogFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    pub fn set_output_to_console(mut self, output_console: bool) -> Self {
+        self.output_console = Some(output_console);
+        self
+    }
+
+    /// Drops the records whose target starts with the `::` segments of `filter`.
+    pub fn add_filter(self, filter: &str) -> Self {
+        self.add_filter_rule(FilterRule::deny().target(filter))
+    }
+
+    /// Appends `rule` to the filter rules of this log, see [`crate::Logger::add_filter_rule`]; they replace the rules of the main log.
+    pub fn add_filter_rule(mut self, rule: FilterRule) -> Self {
+        self.filters.push(rule);
+        self
+    }
+
+    // Routes with their own age or size limit are swept on their own.
+    #[cfg(sfo_log_backend)]
+    pub(crate) fn has_retention(&self) -> bool {
+        self.log_max_age.is_some() || self.log_max_total_size.is_some()
+    }
+}
+
+/// Which records a log added with [`crate::Logger::add_predicate_log`] receives; all conditions have to match.
+#[derive(Debug, Clone)]
+#[cfg_attr(not(sfo_log_backend), allow(dead_code))]
+pub struct LogPredicate {
+    pub(crate) target: Option<String>,
+    pub(crate) levels: (Bound<Level>, Bound<Level>),
+}
+
+impl Default for LogPredicate {
+    fn default() -> Self {
+        Self {
+            target: None,
+            levels: (Bound::Unbounded, Bound::Unbounded),
+        }
+    }
+}
+
+impl LogPredicate {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Matches targets starting with the `::` segments of `target`, where a `*` segment matches any one segment.
+    pub fn target(mut self, target: &str) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    /// Matches records with a level in `levels`, which are ordered from `ERROR` to `TRACE`,
+    /// e.g. `..=Level::WARN` for warnings and errors.
+    pub fn levels(mut self, levels: impl RangeBounds<Level>) -> Self {
+        self.levels = (levels.start_bound().cloned(), levels.end_bound().cloned());
+        self
+    }
+}
+
+/// A target and level condition compiled for matching records.
+#[cfg(sfo_log_backend)]
+pub(crate) struct CompiledPredicate {
+    target: Option<KeyPattern>,
+    levels: (Bound<log::Level>, Bound<log::Level>),
+}
+
+#[cfg(sfo_log_backend)]
+impl CompiledPredicate {
+    pub(crate) fn new(target: Option<&str>, levels: &(Bound<Level>, Bound<Level>)) -> Self {
+        let level = |bound: &Bound<Level>| bound.as_ref().map(log_level);
+        Self {
+            target: target.map(KeyPattern::new),
+            levels: (level(&levels.0), level(&levels.1)),
+        }
+    }
+
+    pub(crate) fn matches(&self, metadata: &log::Metadata) -> bool {
+        self.levels.contains(&metadata.level()) && self.target.as_ref().is_none_or(|v| v.matches(metadata.target()))
+    }
+}
+
+/// A module log added with [`crate::Logger::add_module_log`].
+#[derive(Debug, Clone)]
+#[cfg_attr(not(sfo_log_backend), allow(dead_code))]
+pub(crate) struct ModuleRoute {
+    pub(crate) module: String,
+    pub(crate) name: String,
+    pub(crate) options: ModuleLogOptions,
+}
+
+/// A log added with [`crate::Logger::add_predicate_log`].
+#[derive(Debug, Clone)]
+#[cfg_attr(not(sfo_log_backend), allow(dead_code))]
+pub(crate) struct PredicateRoute {
+    pub(crate) name: String,
+    pub(crate) predicate: LogPredicate,
+    pub(crate) options: ModuleLogOptions,
+}
+
+impl PredicateRoute {
+    // Predicate logs duplicate records of other logs, so they stay off the console by default.
+    pub(crate) fn new(name: &str, predicate: LogPredicate, mut options: ModuleLogOptions) -> Self {
+        options.output_console.get_or_insert(false);
+        Self {
+            name: name.to_string(),
+            predicate,
+            options,
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    children: HashMap<String, Node>,
+    // Child for a `*` segment, matching any single segment.
+    wildcard: Option<Box<Node>>,
+    route: Option<usize>,
+}
+
+// How well a route matched: more segments first, then more literal segments, then the earlier route.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+struct Rank {
+    depth: usize,
+    literals: usize,
+    index: std::cmp::Reverse<usize>,
+}
+
+/// Module keys of the routes compiled into a prefix tree over `::` segments.
+///
+/// `my_app::net` matches `my_app::net` and `my_app::net::tcp` but not `my_app::network`, and the
+/// key with the most matching segments wins regardless of the order the routes were added in.
+/// A `*` segment matches any one segment, e.g. `my_app::*::rpc`.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RouteTable {
+    root: Node,
+}
+
+impl RouteTable {
+    pub(crate) fn new<'a>(keys: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut root = Node::default();
+        for (index, key) in keys.into_iter().enumerate() {
+            let mut node = &mut root;
+            for segment in segments(key) {
+                node = if segment == "*" {
+                    node.wildcard.get_or_insert_with(Default::default)
+                } else {
+                    node.children.entry(segment.to_string()).or_default()
+                };
+            }
+            // The first of several routes with the same key keeps it.
+            node.route.get_or_insert(index);
+        }
+        Self { root }
+    }
+
+    /// Index of the route `target` belongs to.
+    pub(crate) fn find(&self, target: &str) -> Option<usize> {
+        let mut best = None;
+        Self::visit(&self.root, segments(target), 0, 0, &mut best);
+        best.map(|v: Rank| v.index.0)
+    }
+
+    // Called for every record, so the segments are walked with the iterator instead of being collected.
+    fn visit<'a>(node: &Node, mut segments: impl Iterator<Item = &'a str> + Clone, depth: usize, literals: usize, best: &mut Option<Rank>) {
+        if let Some(index) = node.route {
+            let rank = Rank {
+                depth,
+                literals,
+                index: std::cmp::Reverse(index),
+            };
+            if best.is_none_or(|v| rank > v) {
+                *best = Some(rank);
+            }
+        }
+        let Some(segment) = segments.next() else {
+            return;
+        };
+        if let Some(child) = node.children.get(segment) {
+            Self::visit(child, segments.clone(), depth + 1, literals + 1, best);
+        }
+        if let Some(child) = node.wildcard.as_ref() {
+            Self::visit(child, segments, depth + 1, literals, best);
+        }
+    }
+}
+
+/// A single route key or filter target, matched like the keys of a [`RouteTable`].
+#[cfg(sfo_log_backend)]
+pub(crate) struct KeyPattern {
+    segments: Vec<String>,
+}
+
+#[cfg(sfo_log_backend)]
+impl KeyPattern {
+    pub(crate) fn new(key: &str) -> Self {
+        Self {
+            segments: segments(key).map(|v| v.to_string()).collect(),
+        }
+    }
+
+    pub(crate) fn matches(&self, target: &str) -> bool {
+        let mut target = segments(target);
+        self.segments.iter().all(|pattern| target.next().is_some_and(|segment| pattern == "*" || pattern == segment))
+    }
+}
+
+fn segments(path: &str) -> impl Iterator<Item = &str> + Clone {
+    path.split("::").map(|v| v.trim()).filter(|v| !v.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_whole_segments() {
+        let table = RouteTable::new(["my_app::net"]);
+        assert_eq!(table.find("my_app::net"), Some(0));
+        assert_eq!(table.find("my_app::net::tcp"), Some(0));
+        assert_eq!(table.find("my_app::network_utils"), None);
+        assert_eq!(table.find("my_app"), None);
+    }
+
+    #[test]
+    fn longest_prefix_wins_regardless_of_order() {
+        for keys in [["my_app", "my_app::net"], ["my_app::net", "my_app"]] {
+            let table = RouteTable::new(keys);
+            let net = keys.iter().position(|v| *v == "my_app::net");
+            let app = keys.iter().position(|v| *v == "my_app");
+            assert_eq!(table.find("my_app::net::tcp"), net);
+            assert_eq!(table.find("my_app::db"), app);
+        }
+    }
+
+    #[test]
+    fn globs_match_one_segment_and_rank_below_literals() {
+        let table = RouteTable::new(["my_app::*::rpc", "my_app::net::rpc", "my_app::net"]);
+        assert_eq!(table.find("my_app::db::rpc"), Some(0));
+        assert_eq!(table.find("my_app::db::rpc::client"), Some(0));
+        assert_eq!(table.find("my_app::net::rpc"), Some(1));
+        assert_eq!(table.find("my_app::net::tcp"), Some(2));
+        assert_eq!(table.find("my_app::rpc"), None);
+        assert_eq!(table.find("my_app::a::b::rpc"), None);
+    }
+
+    #[test]
+    fn deeper_glob_beats_shorter_literal() {
+        let table = RouteTable::new(["my_app::net", "my_app::*::rpc"]);
+        assert_eq!(table.find("my_app::net::rpc"), Some(1));
+        assert_eq!(table.find("my_app::net::tcp"), Some(0));
+    }
+
+    #[test]
+    fn first_duplicate_key_wins() {
+        let table = RouteTable::new(["my_app::net", "my_app::db", "my_app::net"]);
+        assert_eq!(table.find("my_app::net"), Some(0));
+        assert_eq!(table.find("my_app::db"), Some(1));
+    }
+
+    #[cfg(sfo_log_backend)]
+    #[test]
+    fn key_patterns() {
+        let pattern = KeyPattern::new("my_app::*::rpc");
+        assert!(pattern.matches("my_app::net::rpc::client"));
+        assert!(!pattern.matches("my_app::net"));
+        assert!(!KeyPattern::new("my_app::net").matches("my_app::network_utils"));
+        assert!(KeyPattern::new("").matches("anything"));
+    }
+}
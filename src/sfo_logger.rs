@@ -0,0 +1,432 @@
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock, RwLock, RwLockReadGuard};
+use flexi_logger::filter::{LogLineFilter, LogLineWriter};
+use flexi_logger::{DeferredNow, LoggerHandle, Record};
+use tracing::log;
+use tracing::log::Metadata;
+use crate::async_writer::AsyncQueue;
+use crate::filter::CompiledFilter;
+use crate::line_format::LineFormatter;
+use crate::recent::RecentBuffer;
+use crate::layer::{is_layer_record, log_level};
+use crate::retention::Retention;
+use crate::route::CompiledPredicate;
+use crate::route_table::RouteTable;
+use crate::{Level, LevelDirectives, RouteMode, SfoLogError};
+
+// Formats records for flexi_logger; levels and filter rules are checked in `SfoLogState::log` before.
+pub(crate) struct SfoLogFilter {
+    formatter: Arc<LineFormatter>,
+}
+
+impl SfoLogFilter {
+    pub(crate) fn new(formatter: Arc<LineFormatter>) -> Self {
+        Self {
+            formatter,
+        }
+    }
+}
+
+impl LogLineFilter for SfoLogFilter {
+    fn write(&self, now: &mut DeferredNow, record: &Record, log_line_writer: &dyn LogLineWriter) -> std::io::Result<()> {
+        let mut line = Vec::new();
+        self.formatter.write(&mut line, now, record)?;
+        let line = String::from_utf8_lossy(line.as_slice());
+        log_line_writer.write(now, &Record::builder()
+            .args(format_args!("{}", line))
+            .metadata(record.metadata().clone())
+            .module_path(record.module_path())
+            .file(record.file())
+            .line(record.line())
+            .build())
+    }
+}
+
+pub(crate) struct ModuleLogger {
+    pub(crate) module: String,
+    pub(crate) mode: RouteMode,
+    pub(crate) levels: RwLock<LevelDirectives>,
+    pub(crate) filter: CompiledFilter,
+    pub(crate) logger: Box<dyn log::Log>,
+    pub(crate) handle: LoggerHandle,
+}
+
+/// A log added with [`crate::Logger::add_predicate_log`], receiving every matching record besides the other logs.
+pub(crate) struct PredicateLogger {
+    pub(crate) predicate: CompiledPredicate,
+    pub(crate) levels: RwLock<LevelDirectives>,
+    pub(crate) filter: CompiledFilter,
+    pub(crate) logger: Box<dyn log::Log>,
+    pub(crate) handle: LoggerHandle,
+}
+
+impl PredicateLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.predicate.matches(metadata) && levels_enabled(&self.levels, metadata)
+    }
+}
+
+/// Levels set through [`crate::SfoLogHandle`], applied again to the state rebuilt on reload.
+#[derive(Default)]
+pub(crate) struct LevelOverrides {
+    main: Option<LevelDirectives>,
+    modules: Vec<(String, LevelDirectives)>,
+}
+
+/// The loggers built from one [`crate::Logger`] configuration, swapped as a whole on reload.
+pub(crate) struct SfoLogState {
+    pub(crate) main_logger: Box<dyn log::Log>,
+    pub(crate) main_handle: LoggerHandle,
+    pub(crate) main_levels: RwLock<LevelDirectives>,
+    pub(crate) main_filter: CompiledFilter,
+    pub(crate) module_loggers: Vec<ModuleLogger>,
+    // Module keys of `module_loggers`, by index.
+    pub(crate) routes: RouteTable,
+    pub(crate) predicate_loggers: Vec<PredicateLogger>,
+    pub(crate) recent: Option<Arc<RecentBuffer>>,
+    // Formatter of the main log, used for the records kept in `recent`.
+    pub(crate) formatter: Arc<LineFormatter>,
+    pub(crate) retention: Retention,
+    pub(crate) level_overrides: Mutex<LevelOverrides>,
+}
+
+impl SfoLogState {
+    pub(crate) fn handles(&self) -> impl Iterator<Item = &LoggerHandle> {
+        std::iter::once(&self.main_handle)
+            .chain(self.module_loggers.iter().map(|v| &v.handle))
+            .chain(self.predicate_loggers.iter().map(|v| &v.handle))
+    }
+
+    // Sets log::max_level to the most verbose level of any log, so a module log can be more verbose than the main log.
+    pub(crate) fn sync_max_level(&self) {
+        let max_level = std::iter::once(&self.main_levels)
+            .chain(self.module_loggers.iter().map(|v| &v.levels))
+            .chain(self.predicate_loggers.iter().map(|v| &v.levels))
+            .map(|levels| read_levels(levels).max_level())
+            .max()
+            .flatten();
+        let max_level = max_level.map_or(log::LevelFilter::Off, |v| log_level(&v).to_level_filter());
+        // Levels removed by the `max_level_*` features can't be enabled at runtime.
+        log::set_max_level(max_level.min(log::STATIC_MAX_LEVEL));
+    }
+
+    pub(crate) fn set_main_levels(&self, directives: LevelDirectives) {
+        *self.main_levels.write().unwrap_or_else(|e| e.into_inner()) = directives.clone();
+        self.lock_overrides().main = Some(directives);
+        self.sync_max_level();
+    }
+
+    // Returns `false` if no module log has the key `module_key`.
+    pub(crate) fn set_module_levels(&self, module_key: &str, directives: LevelDirectives) -> bool {
+        let mut found = false;
+        for module_logger in self.module_loggers.iter().filter(|v| v.module == module_key) {
+            *module_logger.levels.write().unwrap_or_else(|e| e.into_inner()) = directives.clone();
+            found = true;
+        }
+        if found {
+            let mut overrides = self.lock_overrides();
+            overrides.modules.retain(|(module, _)| module != module_key);
+            overrides.modules.push((module_key.to_string(), directives));
+            drop(overrides);
+            self.sync_max_level();
+        }
+        found
+    }
+
+    // Takes over the levels set at runtime on `previous`, they win over the levels of the reloaded config.
+    #[cfg(feature = "config")]
+    pub(crate) fn keep_level_overrides(&mut self, previous: &SfoLogState) {
+        let overrides = std::mem::take(&mut *previous.lock_overrides());
+        if let Some(main) = overrides.main.as_ref() {
+            *self.main_levels.get_mut().unwrap_or_else(|e| e.into_inner()) = main.clone();
+        }
+        for (module_key, directives) in overrides.modules.iter() {
+            for module_logger in self.module_loggers.iter_mut().filter(|v| v.module == *module_key) {
+                *module_logger.levels.get_mut().unwrap_or_else(|e| e.into_inner()) = directives.clone();
+            }
+        }
+        *self.level_overrides.get_mut().unwrap_or_else(|e| e.into_inner()) = overrides;
+    }
+
+    fn lock_overrides(&self) -> MutexGuard<'_, LevelOverrides> {
+        self.level_overrides.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn main_enabled(&self, metadata: &Metadata) -> bool {
+        levels_enabled(&self.main_levels, metadata)
+    }
+
+    fn module_logger(&self, target: &str) -> Option<&ModuleLogger> {
+        self.routes.find(target).map(|index| &self.module_loggers[index])
+    }
+
+    // A record goes to the module log with the longest matching key, and to the main log unless the route mode keeps it out.
+    // Predicate logs get every record they match regardless, the recent buffer every record written to any log.
+    pub(crate) fn log(&self, record: &Record) {
+        let mut written = false;
+        for predicate_logger in self.predicate_loggers.iter() {
+            if predicate_logger.enabled(record.metadata()) && predicate_logger.filter.allows(record) {
+                predicate_logger.logger.log(record);
+                written = true;
+            }
+        }
+        let module_logger = self.module_logger(record.target());
+        if let Some(module_logger) = module_logger
+            && levels_enabled(&module_logger.levels, record.metadata())
+            && module_logger.filter.allows(record) {
+            module_logger.logger.log(record);
+            written = true;
+        }
+        if module_logger.is_none_or(|v| v.mode.reaches_main(record.level()))
+            && self.main_enabled(record.metadata())
+            && self.main_filter.allows(record) {
+            self.main_logger.log(record);
+            written = true;
+        }
+        if written && let Some(recent) = self.recent.as_ref() {
+            recent.push(&self.formatter, record);
+        }
+    }
+
+    pub(crate) fn flush(&self) {
+        for handle in self.handles() {
+            handle.flush();
+        }
+    }
+
+    pub(crate) fn shutdown(&self) {
+        for handle in self.handles() {
+            handle.shutdown();
+        }
+    }
+}
+
+impl RouteMode {
+    fn reaches_main(&self, level: log::Level) -> bool {
+        match self {
+            RouteMode::Duplicate => true,
+            RouteMode::Exclusive => false,
+            RouteMode::MainAbove(min_level) => level <= log_level(min_level),
+        }
+    }
+}
+
+fn read_levels(levels: &RwLock<LevelDirectives>) -> RwLockReadGuard<'_, LevelDirectives> {
+    levels.read().unwrap_or_else(|e| e.into_inner())
+}
+
+fn levels_enabled(levels: &RwLock<LevelDirectives>, metadata: &Metadata) -> bool {
+    read_levels(levels).level_for(metadata.target()).is_some_and(|max: Level| metadata.level() <= log_level(&max))
+}
+
+pub(crate) fn read_state(state: &RwLock<SfoLogState>) -> RwLockReadGuard<'_, SfoLogState> {
+    state.read().unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) struct SfoLogger {
+    state: Arc<RwLock<SfoLogState>>,
+    async_queue: Option<Arc<AsyncQueue>>,
+}
+
+impl SfoLogger {
+    pub(crate) fn new(state: Arc<RwLock<SfoLogState>>, async_queue: Option<Arc<AsyncQueue>>) -> Self {
+        Self {
+            state,
+            async_queue,
+        }
+    }
+
+    // Queues the record in async mode, otherwise writes it on the calling thread.
+    pub(crate) fn write(&self, record: &Record) {
+        if self.async_queue.as_ref().is_some_and(|queue| queue.push(record)) {
+            return;
+        }
+        read_state(&self.state).log(record);
+    }
+}
+
+static INSTALLED_LOGGER: OnceLock<&'static SfoLogger> = OnceLock::new();
+
+/// Registers `logger` as the `log` logger; [`crate::SfoLogLayer`] writes through it as well.
+pub(crate) fn install_logger(logger: SfoLogger) -> Result<(), SfoLogError> {
+    let logger: &'static SfoLogger = Box::leak(Box::new(logger));
+    log::set_logger(logger).map_err(|_| SfoLogError::LoggerAlreadySet)?;
+    let _ = INSTALLED_LOGGER.set(logger);
+    Ok(())
+}
+
+pub(crate) fn installed_logger() -> Option<&'static SfoLogger> {
+    INSTALLED_LOGGER.get().copied()
+}
+
+impl log::Log for SfoLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        let state = read_state(&self.state);
+        if state.predicate_loggers.iter().any(|v| v.enabled(metadata)) {
+            return true;
+        }
+        match state.module_logger(metadata.target()) {
+            Some(module_logger) => levels_enabled(&module_logger.levels, metadata)
+                || (module_logger.mode.reaches_main(metadata.level()) && state.main_enabled(metadata)),
+            None => state.main_enabled(metadata),
+        }
+    }
+
+    fn log(&self, record: &Record) {
+        if is_layer_record(record) {
+            return;
+        }
+        self.write(record);
+    }
+
+    fn flush(&self) {
+        if let Some(queue) = self.async_queue.as_ref() {
+            queue.wait_drained();
+        }
+        let state = read_state(&self.state);
+        state.main_logger.flush();
+        for module_logger in state.module_loggers.iter() {
+            module_logger.logger.flush();
+        }
+        for predicate_logger in state.predicate_loggers.iter() {
+            predicate_logger.logger.flush();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use tracing::log::Level;
+    use crate::{FilterRule, LogFormat, LogPredicate, Logger, ModuleLogOptions};
+    use super::*;
+
+    // A logger writing bare messages into a directory of its own, without the console.
+    fn file_logger(name: &str) -> (Logger, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("sfo-log-state-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(dir.as_path());
+        let logger = Logger::new("app")
+            .set_log_to_file(true)
+            .set_log_path(dir.to_str().unwrap())
+            .set_output_to_console(false)
+            .set_log_level("trace")
+            .set_format(LogFormat::Template("{msg}".to_string()));
+        (logger, dir)
+    }
+
+    fn log(state: &SfoLogState, level: Level, target: &str, message: &str) {
+        state.log(&Record::builder().args(format_args!("{}", message)).level(level).target(target).build());
+    }
+
+    // Lines of the log named `log_name`, the main log for an empty name.
+    fn read_log(dir: &std::path::Path, log_name: &str) -> Vec<String> {
+        let base_name = if log_name.is_empty() { "app".to_string() } else { format!("app_{}", log_name) };
+        std::fs::read_to_string(dir.join(format!("{}_rCURRENT.log", base_name)))
+            .unwrap_or_default()
+            .lines()
+            .map(|v| v.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn module_logs_without_rules_use_the_main_rules() {
+        let (logger, dir) = file_logger("main-rules");
+        let state = logger
+            .add_filter_rule(FilterRule::deny().message("^heartbeat"))
+            .add_module_log("my_app::net", "net")
+            .add_module_log_with_options("my_app::db", "db", ModuleLogOptions::new().add_filter("my_app::db::pool"))
+            .add_level_log("error", ..=crate::Level::WARN)
+            .build_state(&dir, false)
+            .unwrap();
+        log(&state, Level::Info, "my_app::net", "heartbeat 1");
+        log(&state, Level::Info, "my_app::net", "sent");
+        log(&state, Level::Warn, "my_app::net", "heartbeat 2");
+        log(&state, Level::Info, "my_app::db", "heartbeat 3");
+        log(&state, Level::Info, "my_app::db::pool", "acquired");
+        state.flush();
+
+        assert_eq!(read_log(&dir, "net"), ["sent"]);
+        // The rules of a module log replace the main rules.
+        assert_eq!(read_log(&dir, "db"), ["heartbeat 3"]);
+        assert_eq!(read_log(&dir, "error"), Vec::<String>::new());
+        assert_eq!(read_log(&dir, ""), ["sent", "acquired"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn route_modes_decide_about_the_main_log() {
+        let (logger, dir) = file_logger("modes");
+        let state = logger
+            .add_module_log("my_app::dup", "dup")
+            .add_module_log_with_mode("my_app::excl", "excl", RouteMode::Exclusive)
+            .add_module_log_with_mode("my_app::above", "above", RouteMode::MainAbove(crate::Level::WARN))
+            .build_state(&dir, false)
+            .unwrap();
+        log(&state, Level::Info, "my_app::dup::a", "dup info");
+        log(&state, Level::Info, "my_app::excl", "excl info");
+        log(&state, Level::Error, "my_app::excl", "excl error");
+        log(&state, Level::Info, "my_app::above", "above info");
+        log(&state, Level::Warn, "my_app::above", "above warn");
+        log(&state, Level::Info, "my_app::other", "other info");
+        state.flush();
+
+        assert_eq!(read_log(&dir, "dup"), ["dup info"]);
+        assert_eq!(read_log(&dir, "excl"), ["excl info", "excl error"]);
+        assert_eq!(read_log(&dir, "above"), ["above info", "above warn"]);
+        assert_eq!(read_log(&dir, ""), ["dup info", "above warn", "other info"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn enabled_is_the_union_of_the_routes() {
+        let (logger, dir) = file_logger("enabled");
+        let state = logger
+            .set_log_level("info")
+            .add_module_log_with_options("my_app::net", "net", ModuleLogOptions::new().set_log_level("trace"))
+            .add_module_log_with_options("my_app::db", "db", ModuleLogOptions::new()
+                .set_log_level("error")
+                .set_mode(RouteMode::MainAbove(crate::Level::WARN)))
+            .add_module_log_with_options("my_app::quiet", "quiet", ModuleLogOptions::new()
+                .set_log_level("off")
+                .set_mode(RouteMode::Exclusive))
+            .add_predicate_log("audit", LogPredicate::new().target("audit").levels(crate::Level::DEBUG..), ModuleLogOptions::new().set_log_level("debug"))
+            .build_state(&dir, false)
+            .unwrap();
+        let logger = SfoLogger::new(Arc::new(RwLock::new(state)), None);
+        let enabled = |level: Level, target: &str| {
+            log::Log::enabled(&logger, &Metadata::builder().level(level).target(target).build())
+        };
+        assert!(enabled(Level::Trace, "my_app::net::tcp"));
+        assert!(enabled(Level::Info, "my_app::other"));
+        assert!(!enabled(Level::Debug, "my_app::other"));
+        // Warnings of the db module reach the main log, info records no log at all.
+        assert!(enabled(Level::Warn, "my_app::db"));
+        assert!(!enabled(Level::Info, "my_app::db"));
+        assert!(!enabled(Level::Error, "my_app::quiet"));
+        assert!(enabled(Level::Debug, "audit::login"));
+        assert!(!enabled(Level::Trace, "audit::login"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn predicate_logs_get_every_matching_record() {
+        let (logger, dir) = file_logger("predicates");
+        let state = logger
+            .add_module_log_with_mode("my_app::net", "net", RouteMode::Exclusive)
+            .add_level_log("error", ..=crate::Level::WARN)
+            .add_predicate_log("net_debug", LogPredicate::new().target("my_app::net").levels(crate::Level::DEBUG..),
+                ModuleLogOptions::new())
+            .build_state(&dir, false)
+            .unwrap();
+        log(&state, Level::Warn, "my_app::net", "net warn");
+        log(&state, Level::Debug, "my_app::net::tcp", "net debug");
+        log(&state, Level::Error, "my_app::db", "db error");
+        log(&state, Level::Trace, "my_app::network", "network trace");
+        state.flush();
+
+        assert_eq!(read_log(&dir, "error"), ["net warn", "db error"]);
+        assert_eq!(read_log(&dir, "net_debug"), ["net debug"]);
+        assert_eq!(read_log(&dir, "net"), ["net warn", "net debug"]);
+        assert_eq!(read_log(&dir, ""), ["db error", "network trace"]);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,127 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, SystemTime};
+use tracing::log;
+use crate::periodic::PeriodicThread;
+use crate::sfo_logger::{read_state, SfoLogState};
+use crate::{Logger, LoggerConfig, SfoLogError};
+
+const CONFIG_WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Polls a config file and rebuilds the running loggers when it changes.
+///
+/// `log_path` is the directory resolved at start, used while neither `base` nor the file set one.
+pub(crate) fn watch_config(base: Logger, path: PathBuf, log_path: PathBuf, state: Arc<RwLock<SfoLogState>>) -> Result<PeriodicThread, SfoLogError> {
+    let mut last_version = file_version(path.as_path());
+    PeriodicThread::spawn("sfo-log-config-watcher", CONFIG_WATCH_INTERVAL, move || {
+        let version = file_version(path.as_path());
+        if version.is_none() || version == last_version {
+            return;
+        }
+        last_version = version;
+        if let Err(e) = reload(&base, path.as_path(), log_path.as_path(), &state) {
+            log::error!("reload log config {} failed: {}", path.display(), e);
+        }
+    })
+}
+
+fn file_version(path: &Path) -> Option<(SystemTime, u64)> {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+fn reload(base: &Logger, path: &Path, log_path: &Path, state: &RwLock<SfoLogState>) -> Result<(), SfoLogError> {
+    let config = LoggerConfig::from_file(path)?;
+    let mut logger = base.clone().apply_config(&config)?;
+    if let Some(env_config) = base.env_config.as_ref() {
+        logger = logger.apply_config(env_config)?;
+    }
+    let log_path = logger.log_path.clone().unwrap_or_else(|| log_path.to_path_buf());
+    let mut new_state = logger.build_state(log_path.as_path(), true)?;
+    // The recent records and the levels set through the handle outlive reloads.
+    let old_state = read_state(state);
+    new_state.recent = old_state.recent.clone();
+    new_state.keep_level_overrides(&old_state);
+    drop(old_state);
+    new_state.sync_max_level();
+    // The previous loggers are flushed and closed once the write lock is released.
+    let _old_state = std::mem::replace(&mut *state.write().unwrap_or_else(|e| e.into_inner()), new_state);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+    use crate::{LogFormat, ModuleLogOptions};
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sfo-log-watch-test-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(dir.as_path());
+        std::fs::create_dir_all(dir.as_path()).unwrap();
+        dir
+    }
+
+    fn logger(dir: &Path) -> Logger {
+        Logger::new("app")
+            .set_log_to_file(true)
+            .set_log_path(dir.to_str().unwrap())
+            .set_output_to_console(false)
+            .set_log_level("info")
+            .set_format(LogFormat::Template("{msg}".to_string()))
+            .add_module_log_with_options("my_app::net", "net", ModuleLogOptions::new().set_log_level("info"))
+    }
+
+    fn main_level(state: &RwLock<SfoLogState>, target: &str) -> Option<crate::Level> {
+        read_state(state).main_levels.read().unwrap().level_for(target)
+    }
+
+    fn module_level(state: &RwLock<SfoLogState>, target: &str) -> Option<crate::Level> {
+        read_state(state).module_loggers[0].levels.read().unwrap().level_for(target)
+    }
+
+    #[test]
+    fn changed_file_is_applied() {
+        let dir = test_dir("applied");
+        let config = dir.join("log.toml");
+        std::fs::write(config.as_path(), "log_level = \"info\"").unwrap();
+        let base = logger(dir.as_path());
+        let state = Arc::new(RwLock::new(base.build_state(dir.as_path(), false).unwrap()));
+        let _watcher = watch_config(base, config.clone(), dir.clone(), state.clone()).unwrap();
+
+        std::fs::write(config.as_path(), "log_level = \"warn,my_app::db=trace\"\nfilters = [\"my_app::noisy\"]").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while main_level(&state, "my_app") != Some(crate::Level::WARN) {
+            assert!(Instant::now() < deadline, "the changed config was not applied");
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(main_level(&state, "my_app::db"), Some(crate::Level::TRACE));
+        let state = read_state(&state);
+        for target in ["my_app::noisy", "my_app::db"] {
+            state.log(&log::Record::builder().args(format_args!("{}", target)).level(log::Level::Warn).target(target).build());
+        }
+        state.flush();
+        assert_eq!(std::fs::read_to_string(dir.join("app_rCURRENT.log")).unwrap(), "my_app::db\n");
+        drop(state);
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn runtime_levels_outlive_reloads() {
+        let dir = test_dir("overrides");
+        let config = dir.join("log.toml");
+        let base = logger(dir.as_path());
+        let state = RwLock::new(base.build_state(dir.as_path(), false).unwrap());
+        read_state(&state).set_main_levels("debug".parse().unwrap());
+        assert!(read_state(&state).set_module_levels("my_app::net", "trace".parse().unwrap()));
+
+        std::fs::write(config.as_path(), "log_level = \"error\"").unwrap();
+        reload(&base, config.as_path(), dir.as_path(), &state).unwrap();
+        assert_eq!(main_level(&state, "my_app"), Some(crate::Level::DEBUG));
+        assert_eq!(module_level(&state, "my_app::net"), Some(crate::Level::TRACE));
+        // The overrides are carried on to every later state.
+        reload(&base, config.as_path(), dir.as_path(), &state).unwrap();
+        assert_eq!(main_level(&state, "my_app"), Some(crate::Level::DEBUG));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
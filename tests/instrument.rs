@@ -0,0 +1,108 @@
+// The logger and the tracing subscriber are global, so the cases share one test function.
+#![cfg(sfo_log_backend)]
+
+use std::fmt::Display;
+use std::future::Future;
+use std::path::Path;
+use std::pin::pin;
+use std::task::{Context, Poll, Waker};
+use sfo_log::{info, instrument, LogFormat, Logger, SfoLogLayer};
+
+fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Conn {
+    peer: u32,
+}
+
+impl Conn {
+    #[instrument(skip(self), fields(peer = self.peer))]
+    fn send(&self, bytes: usize) {
+        info!("sent");
+    }
+
+    #[instrument(skip_all, ret(Display))]
+    async fn recv(&self, bytes: usize) -> usize {
+        info!("received {}", bytes);
+        bytes * 2
+    }
+}
+
+#[instrument]
+fn add(a: u32, b: u32) -> u32 {
+    info!("adding");
+    a + b
+}
+
+#[instrument(err)]
+fn parse(input: &str) -> Result<u32, String> {
+    input.parse().map_err(|_| format!("not a number: {}", input))
+}
+
+#[instrument(name = "display", ret)]
+fn shown(value: u32) -> impl Display {
+    value
+}
+
+#[instrument(level = "debug", err(Display), ret)]
+async fn lookup(key: &str) -> Result<u32, String> {
+    match key {
+        "one" => Ok(1),
+        _ => Err(format!("missing {}", key)),
+    }
+}
+
+fn read_log(dir: &Path) -> Vec<String> {
+    let mut lines = Vec::new();
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let content = std::fs::read_to_string(entry.unwrap().path()).unwrap();
+        lines.extend(content.lines().map(|v| v.to_string()));
+    }
+    lines
+}
+
+#[test]
+fn instrumented_functions_write_their_spans() {
+    let dir = std::env::temp_dir().join(format!("sfo-log-instrument-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(dir.as_path());
+    let handle = Logger::new("instrument")
+        .set_log_to_file(true)
+        .set_log_path(dir.to_str().unwrap())
+        .set_output_to_console(false)
+        .set_log_level("debug")
+        .set_format(LogFormat::Template("{level} {msg}".to_string()))
+        .start()
+        .unwrap();
+    SfoLogLayer::install().unwrap();
+
+    Conn { peer: 7 }.send(3);
+    assert_eq!(block_on(Conn { peer: 7 }.recv(4)), 8);
+    assert_eq!(add(1, 2), 3);
+    assert_eq!(parse("12"), Ok(12));
+    assert!(parse("x").is_err());
+    assert_eq!(shown(5).to_string(), "5");
+    assert_eq!(block_on(lookup("one")), Ok(1));
+    assert!(block_on(lookup("two")).is_err());
+    handle.flush();
+
+    assert_eq!(read_log(dir.as_path()), vec![
+        "INFO send{bytes=3 peer=7}: sent",
+        "INFO recv: received 4",
+        "INFO recv: return=8",
+        "INFO add{a=1 b=2}: adding",
+        "ERROR parse{input=\"x\"}: error=\"not a number: x\"",
+        "INFO display{value=5}: return=5",
+        "DEBUG lookup{key=\"one\"}: return=1",
+        "ERROR lookup{key=\"two\"}: error=missing two",
+    ]);
+    drop(handle);
+    let _ = std::fs::remove_dir_all(dir.as_path());
+}
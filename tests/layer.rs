@@ -0,0 +1,56 @@
+// The logger is global, so the cases share one test function.
+#![cfg(sfo_log_backend)]
+
+use std::path::Path;
+use sfo_log::{info, info_span, warn, LogFormat, Logger, SfoLogLayer};
+use tracing_subscriber::layer::SubscriberExt;
+
+fn read_log(dir: &Path) -> Vec<String> {
+    std::fs::read_to_string(dir.join("layer_rCURRENT.log"))
+        .unwrap()
+        .lines()
+        .map(|v| v.to_string())
+        .collect()
+}
+
+#[test]
+fn layer_writes_each_event_once() {
+    let dir = std::env::temp_dir().join(format!("sfo-log-layer-test-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(dir.as_path());
+    let handle = Logger::new("layer")
+        .set_log_to_file(true)
+        .set_log_path(dir.to_str().unwrap())
+        .set_output_to_console(false)
+        .set_log_level("info")
+        .set_format(LogFormat::Template("{msg}".to_string()))
+        .start()
+        .unwrap();
+
+    tracing::subscriber::with_default(tracing_subscriber::registry().with(SfoLogLayer::new()), || {
+        info!("plain");
+        let span = info_span!("conn", peer = 7);
+        let _guard = span.enter();
+        info!("accepted");
+        info_span!("request", id = 1).in_scope(|| warn!(bytes = 512, "sent"));
+        // The dispatcher of this thread only covers this thread, others still log through `log`.
+        std::thread::spawn(|| info!("other thread")).join().unwrap();
+        std::thread::spawn(|| {
+            tracing::subscriber::with_default(tracing_subscriber::registry(), || info!("without the layer"));
+        }).join().unwrap();
+        info!("after");
+    });
+    info!("no dispatcher");
+    handle.flush();
+
+    assert_eq!(read_log(dir.as_path()), [
+        "plain",
+        "conn{peer=7}: accepted",
+        "conn{peer=7}:request{id=1}: sent bytes=512",
+        "other thread",
+        "without the layer",
+        "conn{peer=7}: after",
+        "no dispatcher",
+    ]);
+    drop(handle);
+    let _ = std::fs::remove_dir_all(dir.as_path());
+}
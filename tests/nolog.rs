@@ -0,0 +1,48 @@
+use std::cell::Cell;
+use sfo_log::{Instrument, Level, LogFormat, Logger, OverflowPolicy, RecentLimit, SfoLogLayer, Span};
+
+#[test]
+fn builder_and_handle_are_no_ops() {
+    let handle = Logger::new("nolog")
+        .set_instance_id("1")
+        .set_log_level("debug")
+        .set_log_to_file(true)
+        .set_format(LogFormat::Json)
+        .set_async(16, OverflowPolicy::DropOldest)
+        .add_module_log("my_crate::net", "net")
+        .set_recent_buffer(RecentLimit::Records(16))
+        .start()
+        .unwrap();
+    handle.set_log_level("trace").unwrap();
+    assert!(!handle.set_module_log_level("my_crate::net", "trace").unwrap());
+    assert_eq!(handle.dropped_records(), 0);
+    assert!(handle.recent(Level::TRACE, "", 16).is_empty());
+    handle.flush();
+    handle.shutdown();
+    SfoLogLayer::install().unwrap();
+}
+
+#[test]
+fn macros_do_not_evaluate_arguments() {
+    let calls = Cell::new(0);
+    let count = || {
+        calls.set(calls.get() + 1);
+        calls.get()
+    };
+    sfo_log::info!("value {}", count());
+    sfo_log::error!(target: "net", id = count(), "failed {:?}", count());
+    sfo_log::event!(Level::WARN, value = %count());
+    let _span = sfo_log::info_span!("request", id = count());
+    assert_eq!(calls.get(), 0);
+}
+
+#[test]
+fn spans_are_disabled() {
+    let span = sfo_log::span!(Level::INFO, "conn", peer = "10.0.0.1");
+    assert!(span.is_disabled());
+    let value = span.in_scope(|| 7);
+    assert_eq!(value, 7);
+    let _entered = span.entered();
+    assert!(Span::current().is_none());
+    let _future = async { 1 }.instrument(Span::none()).in_current_span();
+}